@@ -1,49 +1,85 @@
 use metrics::asana::*;
 use metrics::config::*;
+use metrics::progress::*;
+use metrics::token::*;
 
 use clap::{App, Arg};
-use env_logger;
 use futures::future::{join, join3, join_all};
-use serde_json;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use tokio;
+
+// requests per second, per credential profile
+const MAX_RPS: u16 = 2;
 
 fn main() {
     /* Logging */
     env_logger::init();
 
     /* Command Line */
-    let (config_file_str, token_file_str) = process_command_line();
+    let (config_file_str, token_file_str, dry_run) = process_command_line();
 
     /*
      * Config data
      */
     let config_file_path = Path::new(&config_file_str)
         .canonicalize()
-        .expect(&format!("Bad config file path: {}", &config_file_str));
+        .unwrap_or_else(|_| panic!("Bad config file path: {}", &config_file_str));
     let config_str = fs::read_to_string(config_file_path)
-        .expect(&format!("Bad config file: {}", &config_file_str));
+        .unwrap_or_else(|_| panic!("Bad config file: {}", &config_file_str));
     let config: MyConfig = parse_config(&config_str);
 
     /*
-     * Asana Personal Access Token -- credentials
+     * Asana Personal Access Tokens -- credentials, one per profile in use
      */
-    let token_file_path: PathBuf = Path::new(&token_file_str)
-        .canonicalize()
-        .expect(&format!("Bad token file path: {}", &token_file_str));
-    let token_str =
-        fs::read_to_string(token_file_path).expect(&format!("Bad token file: {}", &token_file_str));
-    let token_str = String::from(token_str.trim_end());
+    // the default profile: --token-file wins over the config file, which wins over $ASANA_TOKEN
+    let default_token_source: TokenSource = match token_file_str {
+        Some(token_file_str) => TokenSource::File(PathBuf::from(token_file_str)),
+        None => config
+            .token
+            .clone()
+            .unwrap_or_else(TokenSource::default_env),
+    };
+    let mut tokens: HashMap<Option<&str>, Token> = HashMap::new();
+    for (label, project_config) in &config.projects {
+        let profile: Option<&str> = project_config.credentials.as_deref();
+        if tokens.contains_key(&profile) {
+            continue;
+        }
+        let token_source = match profile {
+            None => &default_token_source,
+            Some(profile) => config.credentials.get(profile).unwrap_or_else(|| {
+                panic!(
+                    "Project {} uses credentials {} which are not defined in the config",
+                    label, profile
+                )
+            }),
+        };
+        let token: Token = token_source.resolve().unwrap_or_else(|err| {
+            panic!(
+                "Could not get Asana token for credentials {}: {}",
+                profile_name(profile),
+                err
+            )
+        });
+        tokens.insert(profile, token);
+    }
     /*
      * Process
      */
     let mut rt = tokio::runtime::Runtime::new().unwrap();
-    rt.block_on(get_data(&token_str, &config));
+    if dry_run {
+        rt.block_on(estimate_data(&tokens, &config));
+    } else {
+        rt.block_on(get_data(&tokens, &config));
+    }
 }
 
-fn process_command_line() -> (String, String) {
+fn profile_name(profile: Option<&str>) -> &str {
+    profile.unwrap_or("(default)")
+}
+
+fn process_command_line() -> (String, Option<String>, bool) {
     let matches = App::new("fetch")
         .version("0.1.0")
         .author("Parijat Mishra <parijat.mishra@gmail.com>")
@@ -60,7 +96,7 @@ fn process_command_line() -> (String, String) {
                 .short("t")
                 .long("token-file")
                 .takes_value(true)
-                .help("path of file containing an Asana Personal Access Token"),
+                .help("path of file containing an Asana Personal Access Token (default: `token` from the config file, else $ASANA_TOKEN)"),
         )
         .arg(
             Arg::with_name("output-file")
@@ -69,28 +105,115 @@ fn process_command_line() -> (String, String) {
                 .takes_value(true)
                 .help("Output file (JSON data)"),
         )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("count the tasks to fetch and estimate the requests and time needed, without fetching them"),
+        )
         .get_matches();
     let config_file_str = matches
         .value_of("config-file")
         .expect("config-file must be specified");
-    let token_file_str = matches
-        .value_of("token-file")
-        .expect("token-file must be specified");
-    return (config_file_str.to_owned(), token_file_str.to_owned());
+    let token_file_str = matches.value_of("token-file");
+    (
+        config_file_str.to_owned(),
+        token_file_str.map(|s| s.to_owned()),
+        matches.is_present("dry-run"),
+    )
+}
+
+// group projects by credential profile; each profile gets its own client
+// (and rate limit) since Asana limits requests per token
+fn get_profile2projects(config: &MyConfig) -> HashMap<Option<&str>, Vec<&MyProjectConfig>> {
+    let mut profile2projects: HashMap<Option<&str>, Vec<&MyProjectConfig>> = HashMap::new();
+    for project_config in config.projects.values() {
+        profile2projects
+            .entry(project_config.credentials.as_deref())
+            .or_default()
+            .push(project_config);
+    }
+    profile2projects
+}
+
+pub async fn get_data(tokens: &HashMap<Option<&str>, Token>, config: &MyConfig) {
+    let profile2projects = get_profile2projects(config);
+
+    let progress = Progress::new(f64::from(MAX_RPS) * profile2projects.len() as f64);
+    let profile_futures = profile2projects.iter().map(|(profile, project_configs)| {
+        get_profile_data(
+            *profile,
+            &tokens[profile],
+            config,
+            project_configs,
+            &progress,
+        )
+    });
+    let mut data = AsanaData::default();
+    let mut stats = ClientStats::default();
+    for (profile_data, profile_stats) in join_all(profile_futures).await {
+        data.merge(profile_data);
+        stats += profile_stats;
+    }
+    progress.finish();
+
+    let output_filename = "asana_data.json";
+    let output_str = serde_json::to_string(&data).expect("Should convert to JSON string");
+    fs::write(output_filename, output_str).expect("Should write to file");
+
+    println!("Wrote output to file {}.", output_filename);
+    println!(
+        "Fetched {} projects, {} tasks, {} stories and {} users.",
+        data.projects.len(),
+        data.tasks.len(),
+        data.task_stories
+            .iter()
+            .map(|ts| ts.stories.len())
+            .sum::<usize>(),
+        data.users.len()
+    );
+    println!(
+        "Made {} requests ({} retries), received {}, in {}.",
+        stats.requests,
+        stats.retries,
+        format_bytes(stats.bytes),
+        format_duration(progress.elapsed())
+    );
+    if progress.tasks_skipped() > 0 {
+        println!(
+            "Skipped {} tasks because of errors; run with RUST_LOG=warn for details.",
+            progress.tasks_skipped()
+        );
+    }
 }
 
-pub async fn get_data(token: &str, config: &MyConfig) {
-    let client = AsanaClient::new(token, Some(2));
+async fn get_profile_data(
+    profile: Option<&str>,
+    token: &Token,
+    config: &MyConfig,
+    project_configs: &[&MyProjectConfig],
+    progress: &Progress,
+) -> (AsanaData, ClientStats) {
+    log::debug!(
+        "get_profile_data: credentials={} projects={}",
+        profile_name(profile),
+        project_configs.len()
+    );
+    let client = make_client(token, &config.http);
 
     let (asana_projects, asana_project_sections, asana_project_task_gids) =
-        get_asana_data_projects(&client, config).await;
+        get_asana_data_projects(&client, project_configs, progress).await;
 
+    // a task can be in more than one of the projects; fetch it once
+    let mut seen_task_gids: HashSet<&String> = HashSet::new();
     let task_gids: Vec<_> = asana_project_task_gids
         .iter()
         .flat_map(|e| &e.task_gids)
+        .filter(|&gid| seen_task_gids.insert(gid))
         .collect();
+    progress.add_tasks(task_gids.len() as u64);
 
-    let (asana_tasks, asana_task_stories) = get_asana_data_tasks(&client, &task_gids).await;
+    let (asana_tasks, asana_task_stories) =
+        get_asana_data_tasks(&client, &task_gids, progress).await;
 
     let user_gids: HashSet<_> = asana_tasks
         .iter()
@@ -108,16 +231,183 @@ pub async fn get_data(token: &str, config: &MyConfig) {
         tasks: asana_tasks,
         task_stories: asana_task_stories,
     };
-    let output_filename = "asana_data.json";
-    let output_str = serde_json::to_string(&data).expect("Should convert to JSON string");
-    fs::write(output_filename, output_str).expect("Should write to file");
+    (data, client.stats())
+}
 
-    println!("Wrote output to file {}.", output_filename);
+// tasks per credential profile whose stories are fetched to estimate story pages
+const DRY_RUN_STORY_SAMPLE: usize = 5;
+
+struct ProfileEstimate {
+    // one line per project: name, gid and task count
+    project_lines: Vec<String>,
+    tasks: usize,
+    // requests made so far: projects, sections and task lists
+    project_requests: u64,
+    sampled_tasks: usize,
+    story_pages_per_task: f64,
+}
+
+impl ProfileEstimate {
+    // requests for a full fetch, not counting one per distinct assignee
+    fn requests(&self) -> u64 {
+        self.project_requests
+            + (self.tasks as f64 * (1.0 + self.story_pages_per_task)).ceil() as u64
+    }
+}
+
+pub async fn estimate_data(tokens: &HashMap<Option<&str>, Token>, config: &MyConfig) {
+    let profile2projects = get_profile2projects(config);
+
+    let progress = Progress::new(0.0);
+    let profile_futures = profile2projects.iter().map(|(profile, project_configs)| {
+        estimate_profile_data(&tokens[profile], config, project_configs, &progress)
+    });
+    let estimates: Vec<ProfileEstimate> = join_all(profile_futures).await;
+    progress.finish();
+
+    for (profile, estimate) in profile2projects.keys().zip(&estimates) {
+        for project_line in &estimate.project_lines {
+            println!("{}", project_line);
+        }
+        println!(
+            "Credentials {}: {} distinct tasks, {:.1} story pages per task (sampled {} tasks), {} requests",
+            profile_name(*profile),
+            estimate.tasks,
+            estimate.story_pages_per_task,
+            estimate.sampled_tasks,
+            estimate.requests()
+        );
+    }
+
+    // profiles are fetched in parallel, each at its own rate limit
+    let requests: u64 = estimates.iter().map(|e| e.requests()).sum();
+    let max_profile_requests: u64 = estimates.iter().map(|e| e.requests()).max().unwrap_or(0);
+    let duration =
+        std::time::Duration::from_secs_f64(max_profile_requests as f64 / f64::from(MAX_RPS));
+    println!(
+        "Expected: {} tasks, {} requests plus one per distinct assignee, about {} at {} requests per second per token.",
+        estimates.iter().map(|e| e.tasks).sum::<usize>(),
+        requests,
+        format_duration(duration),
+        MAX_RPS
+    );
+}
+
+async fn estimate_profile_data(
+    token: &Token,
+    config: &MyConfig,
+    project_configs: &[&MyProjectConfig],
+    progress: &Progress,
+) -> ProfileEstimate {
+    let client = make_client(token, &config.http);
+
+    let (asana_projects, _, asana_project_task_gids) =
+        get_asana_data_projects(&client, project_configs, progress).await;
+    let project_stats = client.stats();
+
+    let mut seen_task_gids: HashSet<&String> = HashSet::new();
+    let task_gids: Vec<&String> = asana_project_task_gids
+        .iter()
+        .flat_map(|e| &e.task_gids)
+        .filter(|&gid| seen_task_gids.insert(gid))
+        .collect();
+
+    // story counts vary a lot between tasks, so sample tasks spread over the list
+    let sample: Vec<&String> = if task_gids.len() <= DRY_RUN_STORY_SAMPLE {
+        task_gids.clone()
+    } else {
+        (0..DRY_RUN_STORY_SAMPLE)
+            .map(|i| task_gids[i * task_gids.len() / DRY_RUN_STORY_SAMPLE])
+            .collect()
+    };
+    // only the pages of the tasks whose stories were fetched count, as a failed
+    // fetch stops early
+    let mut sampled_tasks = 0usize;
+    let mut story_pages = 0u64;
+    for task_gid in &sample {
+        let before = client.stats();
+        match client.get_task_stories(task_gid).await {
+            Ok(_) => {
+                let after = client.stats();
+                sampled_tasks += 1;
+                story_pages +=
+                    (after.requests - before.requests) - (after.retries - before.retries);
+            }
+            Err(err) => log::warn!("Could not sample stories of task {}: {}", task_gid, err),
+        }
+    }
+    let story_pages_per_task = if sampled_tasks == 0 {
+        1.0
+    } else {
+        story_pages as f64 / sampled_tasks as f64
+    };
+
+    let project_lines = project_configs
+        .iter()
+        .zip(asana_projects.iter().zip(&asana_project_task_gids))
+        .map(|(project_config, (asana_project, project_task_gids))| {
+            format!(
+                "Project {} ({}): {} tasks since {}",
+                asana_project.name,
+                project_config.gid,
+                project_task_gids.task_gids.len(),
+                project_config.horizon
+            )
+        })
+        .collect();
+    ProfileEstimate {
+        project_lines,
+        tasks: task_gids.len(),
+        project_requests: project_stats.requests - project_stats.retries,
+        sampled_tasks,
+        story_pages_per_task,
+    }
+}
+
+fn make_client<'a>(token: &'a Token, http_config: &MyHttpConfig) -> AsanaClient<'a> {
+    let read = |path: &PathBuf| {
+        fs::read(path).unwrap_or_else(|err| panic!("Bad file {}: {}", path.display(), err))
+    };
+    let mut builder = AsanaClient::builder(token)
+        .max_rps(Some(MAX_RPS))
+        .proxy_from_env();
+    if let Some(proxy) = &http_config.proxy {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(no_proxy) = &http_config.no_proxy {
+        builder = builder.no_proxy(no_proxy.clone());
+    }
+    for ca_cert in &http_config.ca_certs {
+        builder = builder.add_root_certificate_pem(read(ca_cert));
+    }
+    match &http_config.client_identity {
+        None => {}
+        Some(MyClientIdentityConfig::Pkcs12 { pkcs12, password }) => {
+            let password = match password {
+                None => Token::new(""),
+                Some(source) => source.resolve().unwrap_or_else(|err| {
+                    panic!("Could not get client certificate password: {}", err)
+                }),
+            };
+            builder = builder.identity(ClientIdentity::Pkcs12 {
+                der: read(pkcs12),
+                password,
+            });
+        }
+        Some(MyClientIdentityConfig::Pem { cert, key }) => {
+            builder = builder.identity(ClientIdentity::Pem {
+                cert: read(cert),
+                key: read(key),
+            });
+        }
+    }
+    builder.build()
 }
 
 async fn get_asana_data_projects(
     client: &AsanaClient<'_>,
-    config: &MyConfig,
+    project_configs: &[&MyProjectConfig],
+    progress: &Progress,
 ) -> (
     Vec<AsanaProject>,
     Vec<AsanaProjectSections>,
@@ -127,34 +417,72 @@ async fn get_asana_data_projects(
     let mut project_sections_futures = Vec::new();
     let mut project_task_gids_futures = Vec::new();
 
-    for (_, project_config) in &config.projects {
+    progress.add_projects(project_configs.len() as u64);
+    for project_config in project_configs {
         project_futures.push(client.get_project(&project_config.gid));
         project_sections_futures.push(client.get_project_sections(&project_config.gid));
-        project_task_gids_futures
-            .push(client.get_project_task_gids(&project_config.gid, &project_config.horizon));
+        // listing the tasks is the slowest part of fetching a project
+        project_task_gids_futures.push(async move {
+            let project_task_gids = client
+                .get_project_task_gids(&project_config.gid, &project_config.horizon)
+                .await;
+            progress.project_done();
+            project_task_gids
+        });
     }
 
-    return join3(
+    join3(
         join_all(project_futures),
         join_all(project_sections_futures),
         join_all(project_task_gids_futures),
     )
-    .await;
+    .await
 }
 
 async fn get_asana_data_tasks(
     client: &AsanaClient<'_>,
-    task_gids: &Vec<&String>,
+    task_gids: &[&String],
+    progress: &Progress,
 ) -> (Vec<AsanaTask>, Vec<AsanaTaskStories>) {
     let mut task_futures = Vec::new();
     let mut task_stories_futures = Vec::new();
 
     for task_gid in task_gids {
-        task_futures.push(client.get_task(&task_gid));
-        task_stories_futures.push(client.get_task_stories(&task_gid));
+        task_futures.push(async move {
+            let task = client.get_task(task_gid).await;
+            progress.task_done();
+            task
+        });
+        task_stories_futures.push(async move {
+            let task_stories = client.get_task_stories(task_gid).await;
+            if let Ok(task_stories) = &task_stories {
+                progress.stories_done(task_stories.stories.len() as u64);
+            }
+            task_stories
+        });
     }
 
-    return join(join_all(task_futures), join_all(task_stories_futures)).await;
+    let (task_results, task_stories_results) =
+        join(join_all(task_futures), join_all(task_stories_futures)).await;
+
+    // a task is kept only if both it and its stories could be fetched
+    let mut tasks = Vec::with_capacity(task_results.len());
+    let mut tasks_stories = Vec::with_capacity(task_stories_results.len());
+    for ((task_gid, task), task_stories) in
+        task_gids.iter().zip(task_results).zip(task_stories_results)
+    {
+        match (task, task_stories) {
+            (Ok(task), Ok(task_stories)) => {
+                tasks.push(task);
+                tasks_stories.push(task_stories);
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                log::warn!("Skipping task {}: {}", task_gid, err);
+                progress.task_skipped();
+            }
+        }
+    }
+    (tasks, tasks_stories)
 }
 
 async fn get_asana_data_users(
@@ -164,8 +492,8 @@ async fn get_asana_data_users(
     let mut user_futures = Vec::new();
 
     for user_gid in user_gids {
-        user_futures.push(client.get_user(&user_gid));
+        user_futures.push(client.get_user(user_gid));
     }
 
-    return join_all(user_futures).await;
+    join_all(user_futures).await
 }
@@ -0,0 +1,241 @@
+use metrics::config::*;
+
+use chrono::NaiveDate;
+use clap::{App, Arg, ArgGroup};
+use metrics::asana::*;
+use metrics::flow::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_TRIALS: u32 = 10000;
+
+fn main() {
+    /* Logging */
+    env_logger::init();
+
+    /* Command Line */
+    let CommandLine {
+        config_file_str,
+        input_file_str,
+        label,
+        target,
+        trials,
+        seed,
+    } = process_command_line();
+
+    /*
+     * Config data
+     */
+    let config_file_path = Path::new(&config_file_str)
+        .canonicalize()
+        .unwrap_or_else(|_| panic!("Bad config file path: {}", &config_file_str));
+    let config_str = fs::read_to_string(config_file_path)
+        .unwrap_or_else(|_| panic!("Bad config file: {}", &config_file_str));
+    let mut config: MyConfig = parse_config(&config_str);
+    // only the forecast project needs processing
+    config
+        .projects
+        .retain(|project_label, _| *project_label == label);
+    if config.projects.is_empty() {
+        panic!("Project {} is not in the config file", label);
+    }
+    // portfolios would refer to the projects left out
+    config.portfolios.clear();
+
+    /*
+     * Input file -- output of `fetch` program
+     */
+    let input_file_path = Path::new(&input_file_str)
+        .canonicalize()
+        .unwrap_or_else(|_| panic!("Bad input file path: {}", &input_file_str));
+    let input_str = fs::read_to_string(input_file_path)
+        .unwrap_or_else(|_| panic!("Bad input file: {}", &input_file_str));
+    let data: AsanaData = serde_json::from_str(&input_str).expect("Invalid output.");
+
+    /*
+     * Process
+     */
+    let report = proc_data(&config, &data);
+    let project = &report.projects[0];
+    let period = &config.projects[&label].period;
+    let throughput = get_throughput_history(&project.cfd.period_counts, period, &project.end);
+    println!(
+        "{}: throughput of {} periods from {} to {}: {:?}",
+        project.name,
+        throughput.len(),
+        project.start.format("%Y-%m-%d"),
+        project.end.format("%Y-%m-%d"),
+        throughput
+    );
+
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    match target {
+        Target::Items(items) => {
+            let forecast = forecast_completion_dates(
+                &throughput,
+                items,
+                period,
+                &project.end,
+                trials,
+                &FORECAST_CONFIDENCE_LEVELS,
+                &mut rng,
+            );
+            println!("When will {} items be done ({} trials)?", items, trials);
+            println!("date        probability  done by then");
+            for outcome in forecast.distribution.iter() {
+                println!(
+                    "{}  {:>10.1}%  {:>11.1}%",
+                    outcome.value,
+                    outcome.probability * 100.0,
+                    outcome.cumulative_probability * 100.0
+                );
+            }
+            for (level, date) in forecast.confidence.iter() {
+                println!("{}% confidence: done by {}", level, date);
+            }
+        }
+        Target::By(by) => {
+            let forecast = forecast_item_counts(
+                &throughput,
+                by,
+                period,
+                &project.end,
+                trials,
+                &FORECAST_CONFIDENCE_LEVELS,
+                &mut rng,
+            );
+            println!("How many items will be done by {} ({} trials)?", by, trials);
+            println!("items  probability  at least this many");
+            for outcome in forecast.distribution.iter() {
+                println!(
+                    "{:>5}  {:>10.1}%  {:>17.1}%",
+                    outcome.value,
+                    outcome.probability * 100.0,
+                    outcome.cumulative_probability * 100.0
+                );
+            }
+            for (level, count) in forecast.confidence.iter() {
+                println!("{}% confidence: at least {} items", level, count);
+            }
+        }
+    }
+}
+
+enum Target {
+    // when will this many items be done?
+    Items(u32),
+    // how many items will be done by this date?
+    By(NaiveDate),
+}
+
+struct CommandLine {
+    config_file_str: String,
+    input_file_str: String,
+    label: String,
+    target: Target,
+    trials: u32,
+    seed: Option<u64>,
+}
+
+fn process_command_line() -> CommandLine {
+    let matches = App::new("forecast")
+        .version("0.1.0")
+        .author("Parijat Mishra <parijat.mishra@gmail.com>")
+        .about("Forecast deliveries from the throughput in the output of `fetch`")
+        .arg(
+            Arg::with_name("config-file")
+                .short("c")
+                .long("config-file")
+                .takes_value(true)
+                .help("path to config file"),
+        )
+        .arg(
+            Arg::with_name("input-file")
+                .short("i")
+                .long("input-file")
+                .takes_value(true)
+                .help("path of file containing the output of the `fetch` program."),
+        )
+        .arg(
+            Arg::with_name("project")
+                .short("p")
+                .long("project")
+                .takes_value(true)
+                .help("key of the project in the config file"),
+        )
+        .arg(
+            Arg::with_name("items")
+                .long("items")
+                .takes_value(true)
+                .help("N: forecast when N more items will be done"),
+        )
+        .arg(
+            Arg::with_name("by")
+                .long("by")
+                .takes_value(true)
+                .help("YYYY-MM-DD: forecast how many items will be done by this date"),
+        )
+        .group(
+            ArgGroup::with_name("target")
+                .args(&["items", "by"])
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("trials")
+                .long("trials")
+                .takes_value(true)
+                .help("number of simulations to run (default 10000)"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("seed for the random numbers, to repeat a forecast exactly"),
+        )
+        .get_matches();
+    let config_file_str = matches
+        .value_of("config-file")
+        .expect("Flag --config-file=PATH must be specified");
+    let input_file_str = matches
+        .value_of("input-file")
+        .expect("Flag --input-file=PATH must be specified");
+    let label = matches
+        .value_of("project")
+        .expect("Flag --project=LABEL must be specified");
+    let target = match (matches.value_of("items"), matches.value_of("by")) {
+        (Some(items_str), _) => match items_str.parse::<u32>() {
+            Ok(items) if items > 0 => Target::Items(items),
+            _ => panic!("Flag --items={} is not a positive number", items_str),
+        },
+        (None, Some(by_str)) => Target::By(
+            NaiveDate::parse_from_str(by_str, "%Y-%m-%d")
+                .unwrap_or_else(|err| panic!("Flag --by={} is not a date: {}", by_str, err)),
+        ),
+        (None, None) => unreachable!("clap requires --items or --by"),
+    };
+    let trials = match matches.value_of("trials") {
+        Some(trials_str) => match trials_str.parse::<u32>() {
+            Ok(trials) if trials > 0 => trials,
+            _ => panic!("Flag --trials={} is not a positive number", trials_str),
+        },
+        None => DEFAULT_TRIALS,
+    };
+    let seed = matches.value_of("seed").map(|seed_str| {
+        seed_str
+            .parse::<u64>()
+            .unwrap_or_else(|_| panic!("Flag --seed={} is not a number", seed_str))
+    });
+    CommandLine {
+        config_file_str: config_file_str.to_owned(),
+        input_file_str: input_file_str.to_owned(),
+        label: label.to_owned(),
+        target,
+        trials,
+        seed,
+    }
+}
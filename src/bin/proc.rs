@@ -1,12 +1,9 @@
 use metrics::config::*;
 
-use chrono::{Date, DateTime, Datelike, TimeZone, Utc, Weekday};
+use chrono::{Datelike, Duration, NaiveDate};
 use clap::{App, Arg};
-use env_logger;
-use lazy_static::lazy_static;
 use metrics::asana::*;
-use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use metrics::flow::*;
 use std::fmt::Write as FmtWrite;
 use std::fs;
 use std::fs::File;
@@ -18,26 +15,57 @@ fn main() {
     env_logger::init();
 
     /* Command Line */
-    let (config_file_str, input_file_str, output_dir_str) = process_command_line();
+    let CommandLine {
+        config_file_str,
+        input_file_str,
+        output_dir_str,
+        from,
+        to,
+    } = process_command_line();
 
     /*
      * Config data
      */
     let config_file_path = Path::new(&config_file_str)
         .canonicalize()
-        .expect(&format!("Bad config file path: {}", &config_file_str));
+        .unwrap_or_else(|_| panic!("Bad config file path: {}", &config_file_str));
     let config_str = fs::read_to_string(config_file_path)
-        .expect(&format!("Bad config file: {}", &config_file_str));
-    let config: MyConfig = parse_config(&config_str);
+        .unwrap_or_else(|_| panic!("Bad config file: {}", &config_file_str));
+    let mut config: MyConfig = parse_config(&config_str);
+    // --from and --to override the report window of every project and portfolio;
+    // --to is inclusive
+    let horizon = from.map(|from| from.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let end = to.map(|to| {
+        (to + Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+    });
+    for project_config in config.projects.values_mut() {
+        if let Some(horizon) = horizon {
+            project_config.horizon = horizon;
+        }
+        if end.is_some() {
+            project_config.end = end;
+        }
+    }
+    for portfolio_config in config.portfolios.values_mut() {
+        if let Some(horizon) = horizon {
+            portfolio_config.horizon = horizon;
+        }
+        if end.is_some() {
+            portfolio_config.end = end;
+        }
+    }
 
     /*
      * Input file -- output of `fetch` program
      */
     let input_file_path = Path::new(&input_file_str)
         .canonicalize()
-        .expect(&format!("Bad input file path: {}", &input_file_str));
-    let input_str =
-        fs::read_to_string(input_file_path).expect(&format!("Bad token file: {}", &input_file_str));
+        .unwrap_or_else(|_| panic!("Bad input file path: {}", &input_file_str));
+    let input_str = fs::read_to_string(input_file_path)
+        .unwrap_or_else(|_| panic!("Bad token file: {}", &input_file_str));
     let data: AsanaData = serde_json::from_str(&input_str).expect("Invalid output.");
 
     /*
@@ -59,25 +87,53 @@ fn main() {
             fs::create_dir_all(&output_dir_path).expect("Could not create output directory");
         }
     }
-    let output_dir_path = output_dir_path.canonicalize().expect(
-        format!(
+    let output_dir_path = output_dir_path.canonicalize().unwrap_or_else(|_| {
+        panic!(
             "Directoruy {} should exist",
             output_dir_path.to_str().unwrap()
         )
-        .as_str(),
-    );
+    });
 
     /*
      * Process
      */
     let report = proc_data(&config, &data);
 
-    for report_project in report.projects {
-        output_gnuplot_data(&report_project, &output_dir_path);
+    let mut report_file_path = output_dir_path.clone();
+    report_file_path.push("report.json");
+    let report_str = serde_json::to_string_pretty(&report).expect("Should convert to JSON string");
+    fs::write(&report_file_path, report_str).expect("Should write to file");
+    println!("Wrote {}", report_file_path.to_str().unwrap());
+
+    output_story_parsing(&report.story_parsing, &output_dir_path);
+
+    for report_project in &report.projects {
+        output_gnuplot_data(report_project, &output_dir_path);
+        output_gnuplot_cumulative_flow(report_project, &output_dir_path);
+        output_gnuplot_flow_rates(report_project, &output_dir_path);
+        output_gnuplot_scatter(report_project, &output_dir_path);
+        output_gnuplot_aging(report_project, &output_dir_path);
+        output_gnuplot_efficiency(report_project, &output_dir_path);
+        output_gnuplot_rework(report_project, &output_dir_path);
+        output_transitions(report_project, &output_dir_path);
+        output_gnuplot_assignees(report_project, &output_dir_path);
+        output_gnuplot_segments(report_project, &output_dir_path);
+        print_unknown_states(report_project);
+    }
+    for portfolio in &report.portfolios {
+        output_gnuplot_portfolio(portfolio, &output_dir_path);
     }
 }
 
-fn process_command_line() -> (String, String, String) {
+struct CommandLine {
+    config_file_str: String,
+    input_file_str: String,
+    output_dir_str: String,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+fn process_command_line() -> CommandLine {
     let matches = App::new("proc")
         .version("0.1.0")
         .author("Parijat Mishra <parijat.mishra@gmail.com>")
@@ -103,6 +159,12 @@ fn process_command_line() -> (String, String, String) {
                 .takes_value(true)
                 .help("path to directory where output files will be stored"),
         )
+        .arg(Arg::with_name("from").long("from").takes_value(true).help(
+            "YYYY-MM-DD: start of the report, instead of each project's and portfolio's `horizon`",
+        ))
+        .arg(Arg::with_name("to").long("to").takes_value(true).help(
+            "YYYY-MM-DD: last day of the report, instead of each project's and portfolio's `end`",
+        ))
         .get_matches();
     let config_file_str = matches
         .value_of("config-file")
@@ -113,351 +175,81 @@ fn process_command_line() -> (String, String, String) {
     let output_dir_str = matches
         .value_of("output-dir")
         .expect("Flag --output-dir=DIRPATH must be specified");
-    return (
-        config_file_str.to_owned(),
-        input_file_str.to_owned(),
-        output_dir_str.to_owned(),
-    );
-}
-
-#[derive(Debug)]
-struct Report<'a> {
-    projects: Vec<Project<'a>>,
-}
-
-#[derive(Debug)]
-struct Project<'a> {
-    label: &'a str,
-    name: &'a str,
-    cfd: Cfd<'a>,
-}
-
-#[derive(Debug)]
-struct Cfd<'a> {
-    cfd_states: Vec<&'a str>,
-    done_states: Vec<&'a str>,
-    period_counts: Vec<PeriodCounts>,
-    period_durations: Vec<PeriodDurations>,
-}
-
-#[derive(Debug)]
-struct PeriodCounts {
-    date: Date<Utc>,
-    cfd_state_counts: Vec<u32>,
-    done_count: u32,
-}
-
-#[derive(Debug)]
-struct PeriodDurations {
-    date: Date<Utc>,
-    p90_duration_seconds: Vec<u64>,
-}
-
-fn proc_data<'a>(config: &'a MyConfig, asana_data: &'a AsanaData) -> Report<'a> {
-    let pnames: HashSet<&str> = get_data_pnames(asana_data);
-    let pgid2pname: HashMap<&str, &str> = get_pgid2pname(asana_data);
-    let sgid2sname: HashMap<&str, &str> = get_sgid2sname(asana_data);
-    let tgid2asana_task: HashMap<&str, &AsanaTask> = get_tgid2asana_task(asana_data);
-    let sgid2pgid: HashMap<&str, &str> = get_sgid2pgid(asana_data);
-    let tgid2pname2sname: HashMap<&str, HashMap<&str, &str>> =
-        get_tgid2pname2sname(&sgid2pgid, &sgid2sname, &pgid2pname, asana_data);
-
-    // capture the times when a task entered a state ("section")
-    // project_name => Vec<(event_time, task gid, state)>
-    let mut pname2t_events: HashMap<&str, Vec<(&DateTime<Utc>, &str, &str)>> = get_task_events(
-        &pnames,
-        &tgid2asana_task,
-        &tgid2pname2sname,
-        &asana_data.task_stories,
-    );
-
-    let mut projects: Vec<Project> = Vec::new();
-
-    for (label, project_config) in &config.projects {
-        println!("Processing: {}", label);
-        let pgid = project_config.gid.as_str();
-        let pname: &str = pgid2pname[pgid];
-        let cfd_states: Vec<&str> = project_config
-            .cfd_states
-            .iter()
-            .map(|s| s.as_str())
-            .collect();
-        let done_states: Vec<&str> = project_config
-            .done_states
-            .iter()
-            .map(|s| s.as_str())
-            .collect();
-        let horizon = &project_config.horizon.iso_week();
-        let events: Vec<(&DateTime<Utc>, &str, &str)> = pname2t_events.remove(pname).unwrap();
-
-        let mut cfd_period_counts: Vec<PeriodCounts> = Vec::new();
-        let mut cfd_period_durations: Vec<PeriodDurations> = Vec::new();
-
-        // ----
-        // last know state of each task, and the timestamp when task entered that state
-        let mut task_latest_state: HashMap<&str, (&str, &DateTime<Utc>)> = HashMap::new();
-        // how many tasks are in each state at the moment
-        let mut state_taskcounts: HashMap<&str, u32> = HashMap::new();
-        // *in this period* how much time did tasks spend in this state
-        let mut state_period_dwelltimes: HashMap<&str, Vec<u64>> = HashMap::new();
-        // *in this period* how many tasks are in states considered to be "Done"
-        // note - there can be multiple states that are considered to conceptually
-        // be Done
-        let mut done_count: u32 = 0;
-
-        // ----
-        let mut start_of_period = Utc
-            .isoywd(horizon.year(), horizon.week(), Weekday::Mon)
-            .and_hms(0, 0, 0);
-        let mut start_of_next_period = start_of_period
-            .checked_add_signed(chrono::Duration::weeks(1))
-            .unwrap();
-        // ----
-
-        for (at, task_gid, sname) in events.into_iter() {
-            while at >= &start_of_next_period {
-                // event in next period -- finalize this period stats and rollover to next period
-                // task -> state ==> count how many times each state appeared
-                for (sname, &timestamp) in task_latest_state.values() {
-                    let count = state_taskcounts.entry(sname).or_insert_with(|| 0);
-                    *count += 1;
-
-                    let dwelltime = (start_of_next_period - timestamp).num_seconds() as u64;
-                    state_period_dwelltimes
-                        .entry(sname)
-                        .or_insert_with(|| Vec::new())
-                        .push(dwelltime);
-                }
-                // extract the counts of the subset of states in `p_counted_states`
-                let state_count_vec: Vec<u32> = cfd_states
-                    .iter()
-                    .map(|&k| *state_taskcounts.get(k).unwrap_or(&0))
-                    .collect();
-                let period_counts = PeriodCounts {
-                    date: start_of_period.date(),
-                    cfd_state_counts: state_count_vec,
-                    done_count: done_count,
-                };
-                cfd_period_counts.push(period_counts);
-
-                // extract the P90 duration of the subsets of states in `p_counted_states`
-                let p90_duration_seconds: Vec<u64> = cfd_states
-                    .iter()
-                    .map(|&k| {
-                        state_period_dwelltimes
-                            .get_mut(k)
-                            .map(|vec| {
-                                vec.sort_unstable();
-                                p90(vec)
-                            })
-                            .unwrap_or(0)
-                    })
-                    .collect();
-                let period_durations = PeriodDurations {
-                    date: start_of_period.date(),
-                    p90_duration_seconds: p90_duration_seconds,
-                };
-                cfd_period_durations.push(period_durations);
-
-                // clear the state_durations because we only count the time
-                // tasks spend in a state within a period
-                state_period_dwelltimes.clear();
-
-                // reset done_count because we only count tasks done
-                // within this period
-                done_count = 0;
-
-                // update loop variables for next period
-                start_of_period = start_of_period
-                    .checked_add_signed(chrono::Duration::weeks(1))
-                    .unwrap();
-                start_of_next_period = start_of_next_period
-                    .checked_add_signed(chrono::Duration::weeks(1))
-                    .unwrap();
-            }
-            // event in current period
-            if let Some((old_state, old_at)) = task_latest_state.insert(task_gid, (sname, at)) {
-                let old_state_duration_seconds = (*at - *old_at).num_seconds() as u64;
-                state_period_dwelltimes
-                    .entry(old_state)
-                    .or_insert_with(|| Vec::new())
-                    .push(old_state_duration_seconds);
-            }
-            if done_states.contains(&sname) {
-                done_count += 1;
-            }
-        }
-
-        let project = Project {
-            label: label,
-            name: pname,
-            cfd: Cfd {
-                cfd_states: cfd_states,
-                done_states: done_states,
-                period_counts: cfd_period_counts,
-                period_durations: cfd_period_durations,
-            },
-        };
-        projects.push(project);
-    }
-    let report = Report { projects };
-
-    return report;
-}
-
-fn p90(vec: &Vec<u64>) -> u64 {
-    let idx = ((vec.len() - 1) as f64 * 0.9) as usize;
-    return *vec.iter().nth(idx).unwrap();
-}
-
-fn get_data_pnames(asana_data: &AsanaData) -> HashSet<&str> {
-    return asana_data
-        .projects
-        .iter()
-        .map(|AsanaProject { name, .. }| name.as_str())
-        .collect();
-}
-
-fn get_pgid2pname(asana_data: &AsanaData) -> HashMap<&str, &str> {
-    return asana_data
-        .projects
-        .iter()
-        .map(|AsanaProject { gid, name, .. }| (gid.as_str(), name.as_str()))
-        .collect();
-}
-
-fn get_sgid2sname(asana_data: &AsanaData) -> HashMap<&str, &str> {
-    return asana_data
-        .project_sections
-        .iter()
-        .flat_map(|aps| {
-            aps.sections
-                .iter()
-                .map(|a_s| (a_s.gid.as_str(), a_s.name.as_str()))
-        })
-        .collect();
-}
-
-fn get_tgid2asana_task(asana_data: &AsanaData) -> HashMap<&str, &AsanaTask> {
-    return asana_data
-        .tasks
-        .iter()
-        .map(|t| (t.gid.as_str(), t))
-        .collect();
-}
-
-fn get_sgid2pgid(asana_data: &AsanaData) -> HashMap<&str, &str> {
-    return asana_data
-        .project_sections
-        .iter()
-        .flat_map(|aps| {
-            aps.sections
-                .iter()
-                .map(move |a_s| (a_s.gid.as_str(), aps.project_gid.as_str()))
+    let parse_date = |flag: &str| {
+        matches.value_of(flag).map(|date_str| {
+            NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+                .unwrap_or_else(|err| panic!("Flag --{}={} is not a date: {}", flag, date_str, err))
         })
-        .collect();
+    };
+    CommandLine {
+        config_file_str: config_file_str.to_owned(),
+        input_file_str: input_file_str.to_owned(),
+        output_dir_str: output_dir_str.to_owned(),
+        from: parse_date("from"),
+        to: parse_date("to"),
+    }
 }
 
-fn get_tgid2pname2sname<'a>(
-    sgid2pgid: &HashMap<&'a str, &'a str>,
-    sgid2sname: &HashMap<&'a str, &'a str>,
-    pgid2pname: &HashMap<&'a str, &'a str>,
-    asana_data: &'a AsanaData,
-) -> HashMap<&'a str, HashMap<&'a str, &'a str>> {
-    let tgid2sgids: HashMap<&str, Vec<&str>> = asana_data
-        .tasks
-        .iter()
-        .map(|a_t| {
-            (
-                a_t.gid.as_str(),
-                a_t.memberships
-                    .iter()
-                    .map(|hm| hm["section"].gid.as_str())
-                    // AsanaTask.membership lists sections from *all* projects a task is in
-                    // not just the ones we are interested in, so filter out the sections
-                    // that con't exist in our `project_sections`
-                    .filter(|sgid| sgid2pgid.contains_key(*sgid))
-                    .collect(),
-            )
-        })
-        .collect();
-
-    let tgid2pname2sname = tgid2sgids
+fn output_story_parsing(story_parsing: &StoryParsing, output_dir_path: &Path) {
+    let template_counts: Vec<String> = story_parsing
+        .templates
         .iter()
-        .map(|(tgid, vec_sgid)| {
-            (
-                *tgid,
-                vec_sgid
-                    .iter()
-                    .map(|sgid| (pgid2pname[sgid2pgid[sgid]], sgid2sname[sgid]))
-                    .collect(),
-            )
-        })
+        .filter(|t| t.count > 0)
+        .map(|t| format!("{} with template {}", t.count, t.template))
         .collect();
-
-    return tgid2pname2sname;
-}
-
-fn get_task_events<'a>(
-    pnames: &'a HashSet<&str>,
-    tgid2asana_task: &'a HashMap<&str, &AsanaTask>,
-    tgid2pname2sname: &'a HashMap<&str, HashMap<&str, &str>>,
-    task_stories: &'a Vec<AsanaTaskStories>,
-) -> HashMap<&'a str, Vec<(&'a DateTime<Utc>, &'a str, &'a str)>> {
-    let mut pname2t_events: HashMap<&str, Vec<(&DateTime<Utc>, &str, &str)>> = HashMap::new();
-
-    // read all the stories and convert them into a timeline of events per project
-    for asana_task_story in task_stories {
-        let task_gid: &str = asana_task_story.task_gid.as_str();
-        let task_created_at = &tgid2asana_task[task_gid].created_at;
-
-        for asana_story in &asana_task_story.stories {
-            if asana_story.resource_subtype.eq("section_changed") {
-                // parse the text of the story
-                let (sname_from, sname_to, pname) = parse_section_changed(&asana_story.text);
-                // event may be for a project we are not interested in
-                if pnames.contains(pname) {
-                    let section_changed_at: &DateTime<Utc> = &asana_story.created_at;
-                    let events = pname2t_events.entry(pname).or_insert_with(|| Vec::new());
-
-                    // if a previous event for this task does not exist, it means we are
-                    // looking at the first section change event -- in that case
-                    // we assume that the task existed in the `sname_from` section at creation.
-                    if events.is_empty() {
-                        events.push((&task_created_at, task_gid, sname_from));
-                    }
-                    // insert the event for section the task moved to
-                    events.push((section_changed_at, task_gid, sname_to));
-                }
-            }
-        }
-
-        // if a task never changed sections after creation, there is no "section changed" story
-        // so we look for such tasks and synthesize the "create" story
-        for pname in tgid2pname2sname[task_gid].keys() {
-            let events = pname2t_events.entry(pname).or_insert_with(|| Vec::new());
-            if events.is_empty() {
-                let task_curr_sname = tgid2pname2sname[task_gid][pname];
-                events.push((task_created_at, task_gid, task_curr_sname));
-            }
-            events.sort_by_cached_key(|entry| entry.0);
+    println!(
+        "Read section changes: {} from story fields{}{}",
+        story_parsing.structured_count,
+        if template_counts.is_empty() { "" } else { ", " },
+        template_counts.join(", ")
+    );
+    for project in story_parsing.unresolved_projects.iter() {
+        if project.ambiguous {
+            println!(
+                "Warning: ignored {} moves of {} tasks in \"{}\", which several projects the tasks are in are named",
+                project.story_count, project.task_count, project.name
+            );
+        } else {
+            println!(
+                "Ignored {} moves of {} tasks in \"{}\", which is not a project in the data that the tasks are in",
+                project.story_count, project.task_count, project.name
+            );
         }
     }
+    if story_parsing.unparsed.is_empty() {
+        return;
+    }
 
-    return pname2t_events;
-}
-
-fn parse_section_changed(text: &str) -> (&str, &str, &str) {
-    lazy_static! {
-        static ref RE: Regex =
-            Regex::new(r#"^moved this Task from "([^"]+?)" to "([^"]+?)" in (.+)$"#).unwrap();
+    // ---------
+    // Unparsed Stories CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "task_gid,created_at,text").unwrap();
+    // record
+    for story in story_parsing.unparsed.iter() {
+        writeln!(
+            &mut buffer,
+            "{},{},\"{}\"",
+            story.task_gid,
+            story.created_at.to_rfc3339(),
+            story.text.replace('"', "\"\"")
+        )
+        .unwrap();
     }
-    let caps = RE.captures(text).unwrap();
-    return (
-        caps.get(1).unwrap().as_str(),
-        caps.get(2).unwrap().as_str(),
-        caps.get(3).unwrap().as_str(),
+    // csv file
+    let unparsed_file_name = "unparsed_stories.csv";
+    let mut unparsed_file_path = PathBuf::from(output_dir_path);
+    unparsed_file_path.push(unparsed_file_name);
+    File::create(&unparsed_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!(
+        "Warning: could not read {} section changes; they are ignored. Add a pattern for them to section_changed_patterns",
+        story_parsing.unparsed.len()
     );
+    println!("Wrote {}", unparsed_file_path.to_str().unwrap());
 }
 
 fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
@@ -478,7 +270,7 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
     for state in cfd_states {
         write!(&mut buffer, " \"{}\"", state).unwrap();
     }
-    write!(&mut buffer, "\n").unwrap();
+    writeln!(&mut buffer).unwrap();
     // record
     for period_count in report_project.cfd.period_counts.iter() {
         let date = period_count.date;
@@ -494,7 +286,7 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
         for count in period_count.cfd_state_counts.iter() {
             write!(&mut buffer, " {}", count).unwrap();
         }
-        write!(&mut buffer, "\n").unwrap();
+        writeln!(&mut buffer).unwrap();
     }
     // data file
     let cfd_data_file_name = format!("{}_cfd.dat", label);
@@ -507,15 +299,16 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
     println!("Wrote {}", cfd_data_file_path.to_str().unwrap());
 
     // ---------
-    // P90 Durations Data File
+    // Durations Data File
     // ---------
+    let percentiles = &report_project.cfd.percentiles;
     let mut buffer = String::new();
     // header
     write!(&mut buffer, "# date").unwrap();
     for state in cfd_states {
-        write!(&mut buffer, " \"{}\"", state).unwrap();
+        write_stats_header(&mut buffer, state, percentiles);
     }
-    write!(&mut buffer, "\n").unwrap();
+    writeln!(&mut buffer).unwrap();
     // record
     for period_durations in report_project.cfd.period_durations.iter() {
         let date = period_durations.date;
@@ -527,18 +320,13 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
             date.day()
         )
         .unwrap();
-        for duration in period_durations.p90_duration_seconds.iter() {
-            write!(
-                &mut buffer,
-                " {}",
-                (*duration as f32) / (24.0 * 60.0 * 60.0)
-            )
-            .unwrap();
+        for stats in period_durations.duration_seconds.iter() {
+            write_stats_days(&mut buffer, stats);
         }
-        write!(&mut buffer, "\n").unwrap();
+        writeln!(&mut buffer).unwrap();
     }
     // data file
-    let duration_data_file_name = format!("{}_p90_durations.dat", label);
+    let duration_data_file_name = format!("{}_durations.dat", label);
     let mut duration_data_file_path = PathBuf::from(output_dir_path);
     duration_data_file_path.push(&duration_data_file_name);
     File::create(&duration_data_file_path)
@@ -577,6 +365,134 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
         .unwrap();
     println!("Wrote {}", done_count_data_file_path.to_str().unwrap());
 
+    // ---------
+    // WIP Limit Breaches CSV File
+    // ---------
+    if !report_project.wip_limits.is_empty() {
+        let mut buffer = String::new();
+        // header
+        writeln!(
+            &mut buffer,
+            "state,wip_limit,start,end,days,max_count,ongoing"
+        )
+        .unwrap();
+        // record
+        for wip_limit in report_project.wip_limits.iter() {
+            println!(
+                "WIP limit of {} ({}): exceeded {} times, for {:.1} days",
+                wip_limit.state,
+                wip_limit.limit,
+                wip_limit.breaches.len(),
+                seconds_to_days(wip_limit.breached_seconds)
+            );
+            for breach in wip_limit.breaches.iter() {
+                writeln!(
+                    &mut buffer,
+                    "\"{}\",{},{},{},{},{},{}",
+                    wip_limit.state.replace('"', "\"\""),
+                    wip_limit.limit,
+                    breach.start.to_rfc3339(),
+                    breach.end.to_rfc3339(),
+                    seconds_to_days(breach.seconds),
+                    breach.max_count,
+                    breach.ongoing
+                )
+                .unwrap();
+            }
+        }
+        // csv file
+        let breaches_file_name = format!("{}_wip_breaches.csv", label);
+        let mut breaches_file_path = PathBuf::from(output_dir_path);
+        breaches_file_path.push(&breaches_file_name);
+        File::create(&breaches_file_path)
+            .unwrap()
+            .write_all(buffer.as_bytes())
+            .unwrap();
+        println!("Wrote {}", breaches_file_path.to_str().unwrap());
+    }
+
+    // ---------
+    // Task Times CSV File
+    // ---------
+    let cycle_times = &report_project.cycle_times;
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "gid,name,created_at,committed_at,delivered_at,cycle_time_days,lead_time_days"
+    )
+    .unwrap();
+    // record
+    for task_times in cycle_times.tasks.iter() {
+        writeln!(
+            &mut buffer,
+            "{},\"{}\",{},{},{},{},{}",
+            task_times.task_gid,
+            task_times.name.replace('"', "\"\""),
+            task_times.created_at.to_rfc3339(),
+            task_times
+                .committed_at
+                .map(|at| at.to_rfc3339())
+                .unwrap_or_default(),
+            task_times
+                .delivered_at
+                .map(|at| at.to_rfc3339())
+                .unwrap_or_default(),
+            task_times
+                .cycle_time_seconds
+                .map(|seconds| seconds_to_days(seconds).to_string())
+                .unwrap_or_default(),
+            task_times
+                .lead_time_seconds
+                .map(|seconds| seconds_to_days(seconds).to_string())
+                .unwrap_or_default()
+        )
+        .unwrap();
+    }
+    // csv file
+    let task_times_file_name = format!("{}_task_times.csv", label);
+    let mut task_times_file_path = PathBuf::from(output_dir_path);
+    task_times_file_path.push(&task_times_file_name);
+    File::create(&task_times_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", task_times_file_path.to_str().unwrap());
+
+    // ---------
+    // Cycle and Lead Time Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(&mut buffer, "# date").unwrap();
+    write_stats_header(&mut buffer, "cycle", &cycle_times.percentiles);
+    write_stats_header(&mut buffer, "lead", &cycle_times.percentiles);
+    writeln!(&mut buffer).unwrap();
+    // record
+    for period_task_times in cycle_times.periods.iter() {
+        let date = period_task_times.date;
+        write!(
+            &mut buffer,
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month(),
+            date.day()
+        )
+        .unwrap();
+        write_stats_days(&mut buffer, &period_task_times.cycle_time_seconds);
+        write_stats_days(&mut buffer, &period_task_times.lead_time_seconds);
+        writeln!(&mut buffer).unwrap();
+    }
+    // data file
+    let cycle_times_data_file_name = format!("{}_cycle_times.dat", label);
+    let mut cycle_times_data_file_path = PathBuf::from(output_dir_path);
+    cycle_times_data_file_path.push(&cycle_times_data_file_name);
+    File::create(&cycle_times_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", cycle_times_data_file_path.to_str().unwrap());
+
     // ---------
     // Gnuplot
     // ---------
@@ -584,35 +500,51 @@ fn output_gnuplot_data(report_project: &Project, output_dir_path: &Path) {
     writeln!(
         &mut buffer,
         r#"
-set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,1024
 set output "{label}.png"
-set multiplot layout 3,1 title "{name}""#,
+set multiplot layout 4,1 title "{name}""#,
         label = label,
         name = name
     )
     .unwrap();
-    // CFD - Counts
+    // CFD - Counts, with a red band wherever a WIP limit was exceeded
     writeln!(
         &mut buffer,
         r#"# CFD
-set title "Cumulative Tasks in State - Count"
+set title "Work in Progress - Tasks in State, Stacked - Count"
 set key left top outside
 set xdata time
-set timefmt "%Y-%m-%d"
-{plotline}"#,
-        plotline = make_gnuplot_cfdline(&cfd_data_file_name, &cfd_states)
+set timefmt "%Y-%m-%d""#
+    )
+    .unwrap();
+    for wip_limit in report_project.wip_limits.iter() {
+        for breach in wip_limit.breaches.iter() {
+            writeln!(
+                &mut buffer,
+                r#"set object rect from strptime("{fmt}", "{start}"), graph 0 to strptime("{fmt}", "{end}"), graph 1 front fc rgb "red" fs transparent solid 0.15 noborder"#,
+                fmt = "%Y-%m-%dT%H:%M:%S",
+                start = breach.start.format("%Y-%m-%dT%H:%M:%S"),
+                end = breach.end.format("%Y-%m-%dT%H:%M:%S")
+            )
+            .unwrap();
+        }
+    }
+    writeln!(
+        &mut buffer,
+        r#"{plotline}unset object"#,
+        plotline = make_gnuplot_cfdline(&cfd_data_file_name, cfd_states)
     )
     .unwrap();
-    // P90 Durations (Hours)
+    // Durations (Days)
     writeln!(
         &mut buffer,
-        r#"# P90 Duration (Days)
-set title "P90 Age Tasks in State - Days"
+        r#"# Duration (Days)
+set title "Age of Tasks in State - Days"
 set key left top outside
 set xdata time
 set timefmt "%Y-%m-%d"
 {plotline}"#,
-        plotline = make_gnuplot_cfdline(&duration_data_file_name, &cfd_states)
+        plotline = make_gnuplot_durations_line(&duration_data_file_name, cfd_states, percentiles)
     )
     .unwrap();
     // Task "Done" per period
@@ -628,6 +560,21 @@ plot "{data_file_name}" using 1:2 with filledcurve x1"#,
         data_file_name = done_count_data_file_name
     )
     .unwrap();
+    // Cycle time percentiles of the tasks delivered in each period
+    writeln!(
+        &mut buffer,
+        r#"# Cycle Time (Days)
+set title "Cycle Time - {commitment_point} to {delivery_state_names} - Days"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+{plotline}"#,
+        commitment_point = cycle_times.commitment_point,
+        delivery_state_names = cycle_times.delivery_states.join(", "),
+        plotline =
+            make_gnuplot_cycle_time_line(&cycle_times_data_file_name, &cycle_times.percentiles)
+    )
+    .unwrap();
 
     // gnuplot file
     let gnuplot_file_name = format!("{}.gnuplot", label);
@@ -638,28 +585,1620 @@ plot "{data_file_name}" using 1:2 with filledcurve x1"#,
     println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
 }
 
-fn make_gnuplot_cfdline(file_name: &str, states: &Vec<&str>) -> String {
-    let mut buffer = String::from("plot");
-    // gnuplot: columns in data files start from 1
-    // col 1 is the date col; state cols are 2, 3, ... states.len() + 1
-    let max_gnuplot_col = states.len() + 1;
-    for (idx, state) in states.iter().enumerate() {
-        // idx starts from 0
+fn output_gnuplot_cumulative_flow(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let cfd_states = &report_project.cfd.cfd_states;
+
+    // ---------
+    // Cumulative Arrivals Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(&mut buffer, "# date").unwrap();
+    for state in cfd_states {
+        write!(&mut buffer, " \"{}\"", state).unwrap();
+    }
+    writeln!(&mut buffer).unwrap();
+    // record
+    for period_counts in report_project.cfd.period_cumulative_counts.iter() {
+        let date = period_counts.date;
+        write!(
+            &mut buffer,
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month(),
+            date.day()
+        )
+        .unwrap();
+        for count in period_counts.cumulative_counts.iter() {
+            write!(&mut buffer, " {}", count).unwrap();
+        }
+        writeln!(&mut buffer).unwrap();
+    }
+    // data file
+    let data_file_name = format!("{}_cumulative_flow.dat", label);
+    let mut data_file_path = PathBuf::from(output_dir_path);
+    data_file_path.push(&data_file_name);
+    File::create(&data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    // the earliest state has the most arrivals, so it is drawn first and the later
+    // states on top of it
+    let mut plotline = String::from("plot");
+    for (idx, state) in cfd_states.iter().enumerate() {
         if idx > 0 {
-            write!(&mut buffer, ",").unwrap()
+            write!(&mut plotline, ",").unwrap()
         };
-        let gnuplot_column = idx + 2;
         write!(
-            &mut buffer,
-            r#" "{file_name}" using 1:({col}) with filledcurve x1 title "{state}""#,
-            file_name = file_name,
-            col = make_col_expression(gnuplot_column as u32, max_gnuplot_col as u32),
+            &mut plotline,
+            r#" "{file_name}" using 1:{col} with filledcurve x1 title "{state}""#,
+            file_name = data_file_name,
+            col = idx + 2,
             state = state
         )
         .unwrap();
     }
-    write!(&mut buffer, "\n").unwrap();
-    return buffer;
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_cumulative_flow.png"
+set title "{name} - Cumulative Flow - Tasks That Reached Each State - Count"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+{plotline}"#,
+        label = label,
+        name = name,
+        plotline = plotline
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_cumulative_flow.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_flow_rates(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let flow_rates = &report_project.flow_rates;
+
+    // ---------
+    // Arrivals and Departures Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "# date arrivals departures net_flow cumulative_net_flow arrivals_average departures_average"
+    )
+    .unwrap();
+    // record
+    for period_flow in flow_rates.periods.iter() {
+        let date = period_flow.date;
+        writeln!(
+            &mut buffer,
+            "{:04}-{:02}-{:02} {} {} {} {} {:.2} {:.2}",
+            date.year(),
+            date.month(),
+            date.day(),
+            period_flow.arrivals,
+            period_flow.departures,
+            period_flow.net_flow,
+            period_flow.cumulative_net_flow,
+            period_flow.arrivals_average,
+            period_flow.departures_average
+        )
+        .unwrap();
+    }
+    // data file
+    let flow_rates_data_file_name = format!("{}_flow_rates.dat", label);
+    let mut flow_rates_data_file_path = PathBuf::from(output_dir_path);
+    flow_rates_data_file_path.push(&flow_rates_data_file_name);
+    File::create(&flow_rates_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", flow_rates_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_flow_rates.png"
+set multiplot layout 2,1 title "{name}""#,
+        label = label,
+        name = name
+    )
+    .unwrap();
+    // Arrivals and departures per period, with their rolling averages
+    writeln!(
+        &mut buffer,
+        r#"# Arrivals and Departures
+set title "Arrivals and Departures - Count per Period, {rolling}-Period Rolling Average"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+plot "{file_name}" using 1:2 with impulses lw 2 lc 1 title "Arrivals", "{file_name}" using 1:3 with impulses lw 2 lc 2 title "Departures", "{file_name}" using 1:6 with lines lw 2 lc 1 title "Arrivals (avg)", "{file_name}" using 1:7 with lines lw 2 lc 2 title "Departures (avg)""#,
+        rolling = flow_rates.rolling_average_periods,
+        file_name = flow_rates_data_file_name
+    )
+    .unwrap();
+    // The backlog grows while the cumulative net flow goes up
+    writeln!(
+        &mut buffer,
+        r#"# Cumulative Net Flow
+set title "Backlog Growth - Cumulative Arrivals minus Departures - Count"
+unset key
+set xdata time
+set timefmt "%Y-%m-%d"
+plot "{file_name}" using 1:5 with filledcurve y1=0"#,
+        file_name = flow_rates_data_file_name
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_flow_rates.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_scatter(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let cycle_times = &report_project.cycle_times;
+
+    let delivered: Vec<&TaskTimes> = cycle_times
+        .tasks
+        .iter()
+        .filter(|t| t.cycle_time_seconds.is_some())
+        .collect();
+    if delivered.is_empty() {
+        println!("No tasks delivered in {}, skipping the scatterplot", label);
+        return;
+    }
+
+    // ---------
+    // Scatterplot Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "# delivered_at cycle_time_days name").unwrap();
+    // record
+    for task_times in delivered {
+        writeln!(
+            &mut buffer,
+            "{} {} \"{}\"",
+            task_times.delivered_at.unwrap().format("%Y-%m-%dT%H:%M:%S"),
+            seconds_to_days(task_times.cycle_time_seconds.unwrap()),
+            // gnuplot strings cannot contain double quotes
+            task_times.name.replace('"', "'")
+        )
+        .unwrap();
+    }
+    // data file
+    let scatter_data_file_name = format!("{}_scatter.dat", label);
+    let mut scatter_data_file_path = PathBuf::from(output_dir_path);
+    scatter_data_file_path.push(&scatter_data_file_name);
+    File::create(&scatter_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", scatter_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_scatter.png"
+set title "{name} - Cycle Time - {commitment_point} to {delivery_state_names} - Days"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%dT%H:%M:%S"
+set format x "%Y-%m-%d"
+set ylabel "Days""#,
+        label = label,
+        name = name,
+        commitment_point = cycle_times.commitment_point,
+        delivery_state_names = cycle_times.delivery_states.join(", ")
+    )
+    .unwrap();
+    // one horizontal line per percentile; tasks above the highest are outliers
+    let mut plotline = format!(
+        r#"plot "{}" using 1:2 with points pt 7 title "Tasks""#,
+        scatter_data_file_name
+    );
+    let mut outlier_days = None;
+    for (pct, seconds) in cycle_times
+        .percentiles
+        .iter()
+        .zip(cycle_times.cycle_time_seconds.percentiles.iter())
+    {
+        let days = seconds_to_days(seconds.unwrap());
+        writeln!(&mut buffer, "P{} = {}", pct, days).unwrap();
+        write!(
+            &mut plotline,
+            r#", P{pct} with lines title "P{pct} ({days:.1} days)""#,
+            pct = pct,
+            days = days
+        )
+        .unwrap();
+        outlier_days = Some(days);
+    }
+    if let Some(outlier_days) = outlier_days {
+        write!(
+            &mut plotline,
+            r#", "{}" using 1:($2 > {} ? $2 : 1/0):3 with labels left offset 1,0 font ",8" notitle"#,
+            scatter_data_file_name, outlier_days
+        )
+        .unwrap();
+    }
+    writeln!(&mut buffer, "{}", plotline).unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_scatter.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_aging(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let aging_wip = &report_project.aging_wip;
+
+    // ---------
+    // Aging WIP CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "gid,name,assignee,state,committed_at,age_days,above_p{}",
+        AGING_WIP_ALERT_PERCENTILE
+    )
+    .unwrap();
+    // record
+    for task in aging_wip.tasks.iter() {
+        writeln!(
+            &mut buffer,
+            "{},\"{}\",\"{}\",\"{}\",{},{},{}",
+            task.task_gid,
+            task.name.replace('"', "\"\""),
+            task.assignee.unwrap_or_default().replace('"', "\"\""),
+            task.state.replace('"', "\"\""),
+            task.committed_at.to_rfc3339(),
+            seconds_to_days(task.age_seconds),
+            task.alert
+        )
+        .unwrap();
+    }
+    // csv file
+    let aging_file_name = format!("{}_aging_wip.csv", label);
+    let mut aging_file_path = PathBuf::from(output_dir_path);
+    aging_file_path.push(&aging_file_name);
+    File::create(&aging_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", aging_file_path.to_str().unwrap());
+
+    for task in aging_wip.tasks.iter().filter(|t| t.alert) {
+        println!(
+            "Aging above P{} in {}: {} ({}), {:.1} days",
+            AGING_WIP_ALERT_PERCENTILE,
+            task.state,
+            task.name,
+            task.assignee.unwrap_or("unassigned"),
+            seconds_to_days(task.age_seconds)
+        );
+    }
+
+    if aging_wip.states.is_empty() {
+        println!(
+            "No in-progress states in {}, skipping the aging chart",
+            label
+        );
+        return;
+    }
+
+    // ---------
+    // Aging States Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(&mut buffer, "# idx state").unwrap();
+    write_stats_header(&mut buffer, "age", &aging_wip.percentiles);
+    writeln!(&mut buffer).unwrap();
+    // record
+    for (idx, aging_state) in aging_wip.states.iter().enumerate() {
+        write!(&mut buffer, "{} \"{}\"", idx, aging_state.state).unwrap();
+        write_stats_days(&mut buffer, &aging_state.age_seconds);
+        writeln!(&mut buffer).unwrap();
+    }
+    // data file
+    let states_data_file_name = format!("{}_aging_states.dat", label);
+    let mut states_data_file_path = PathBuf::from(output_dir_path);
+    states_data_file_path.push(&states_data_file_name);
+    File::create(&states_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", states_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Aging Tasks Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "# idx age_days alert label").unwrap();
+    // record
+    for task in aging_wip.tasks.iter() {
+        let idx = aging_wip
+            .states
+            .iter()
+            .position(|s| s.state == task.state)
+            .unwrap();
+        writeln!(
+            &mut buffer,
+            "{} {} {} \"{} ({})\"",
+            idx,
+            seconds_to_days(task.age_seconds),
+            task.alert as u8,
+            // gnuplot strings cannot contain double quotes
+            task.name.replace('"', "'"),
+            task.assignee.unwrap_or("unassigned").replace('"', "'")
+        )
+        .unwrap();
+    }
+    // data file
+    let tasks_data_file_name = format!("{}_aging_tasks.dat", label);
+    let mut tasks_data_file_path = PathBuf::from(output_dir_path);
+    tasks_data_file_path.push(&tasks_data_file_name);
+    File::create(&tasks_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", tasks_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_aging.png"
+set title "{name} - Aging Work in Progress as of {as_of} - Days Since Commitment"
+set key left top outside
+set style fill transparent solid 0.25 noborder
+set boxwidth 0.8
+set xrange [-0.5:{max_x}]
+set yrange [0:*]
+set ylabel "Days""#,
+        label = label,
+        name = name,
+        as_of = aging_wip.as_of.format("%Y-%m-%d"),
+        max_x = aging_wip.states.len() as f32 - 0.5
+    )
+    .unwrap();
+    // draw the widest band first, so that the narrower ones show on top of it
+    let mut plotline = String::from("plot");
+    for (idx, pct) in aging_wip.percentiles.iter().enumerate().rev() {
+        write!(
+            &mut plotline,
+            r#" "{file_name}" using 1:{col}:xtic(2) with boxes title "P{pct}","#,
+            file_name = states_data_file_name,
+            col = 3 + STATS_COLUMNS.len() + idx,
+            pct = pct
+        )
+        .unwrap();
+    }
+    write!(
+        &mut plotline,
+        r#" "{file_name}" using 1:2 with points pt 7 title "Tasks", "{file_name}" using 1:($3 > 0 ? $2 : 1/0):4 with labels left offset 1,0 font ",8" notitle"#,
+        file_name = tasks_data_file_name
+    )
+    .unwrap();
+    writeln!(&mut buffer, "{}", plotline).unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_aging.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_efficiency(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let flow_efficiency = &report_project.flow_efficiency;
+
+    if flow_efficiency.waiting_states.is_empty() {
+        println!("No waiting_states in {}, skipping flow efficiency", label);
+        return;
+    }
+    let percent = |efficiency: Option<f64>| match efficiency {
+        Some(efficiency) => format!("{:.1}", efficiency * 100.0),
+        None => String::from("NaN"),
+    };
+
+    // ---------
+    // Task Flow Efficiency CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "gid,name,delivered_at,active_days,waiting_days,flow_efficiency_percent"
+    )
+    .unwrap();
+    // record
+    for task in flow_efficiency.tasks.iter() {
+        writeln!(
+            &mut buffer,
+            "{},\"{}\",{},{},{},{}",
+            task.task_gid,
+            task.name.replace('"', "\"\""),
+            task.delivered_at.to_rfc3339(),
+            seconds_to_days(task.active_seconds),
+            seconds_to_days(task.waiting_seconds),
+            task.efficiency
+                .map(|efficiency| format!("{:.1}", efficiency * 100.0))
+                .unwrap_or_default()
+        )
+        .unwrap();
+    }
+    // csv file
+    let tasks_file_name = format!("{}_flow_efficiency.csv", label);
+    let mut tasks_file_path = PathBuf::from(output_dir_path);
+    tasks_file_path.push(&tasks_file_name);
+    File::create(&tasks_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", tasks_file_path.to_str().unwrap());
+
+    // ---------
+    // Period Flow Efficiency Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "# date delivered_count active_days waiting_days flow_efficiency_percent"
+    )
+    .unwrap();
+    // record
+    for period_efficiency in flow_efficiency.periods.iter() {
+        let date = period_efficiency.date;
+        writeln!(
+            &mut buffer,
+            "{:04}-{:02}-{:02} {} {} {} {}",
+            date.year(),
+            date.month(),
+            date.day(),
+            period_efficiency.delivered_count,
+            seconds_to_days(period_efficiency.active_seconds),
+            seconds_to_days(period_efficiency.waiting_seconds),
+            percent(period_efficiency.efficiency)
+        )
+        .unwrap();
+    }
+    // data file
+    let periods_data_file_name = format!("{}_flow_efficiency.dat", label);
+    let mut periods_data_file_path = PathBuf::from(output_dir_path);
+    periods_data_file_path.push(&periods_data_file_name);
+    File::create(&periods_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", periods_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Wait States Data File
+    // ---------
+    println!(
+        "Flow efficiency of {}: {}%",
+        label,
+        percent(flow_efficiency.efficiency)
+    );
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "# idx state days share_percent task_count").unwrap();
+    // record
+    for (idx, wait_state) in flow_efficiency.wait_states.iter().enumerate() {
+        writeln!(
+            &mut buffer,
+            "{} \"{}\" {} {} {}",
+            idx,
+            wait_state.state,
+            seconds_to_days(wait_state.seconds),
+            percent(wait_state.share),
+            wait_state.task_count
+        )
+        .unwrap();
+        println!(
+            "Waiting in {}: {:.1} days, {}% of cycle time, {} tasks",
+            wait_state.state,
+            seconds_to_days(wait_state.seconds),
+            percent(wait_state.share),
+            wait_state.task_count
+        );
+    }
+    // data file
+    let wait_states_data_file_name = format!("{}_wait_states.dat", label);
+    let mut wait_states_data_file_path = PathBuf::from(output_dir_path);
+    wait_states_data_file_path.push(&wait_states_data_file_name);
+    File::create(&wait_states_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", wait_states_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_flow_efficiency.png"
+set multiplot layout 2,1 title "{name}""#,
+        label = label,
+        name = name
+    )
+    .unwrap();
+    // Flow efficiency of the tasks delivered in each period
+    writeln!(
+        &mut buffer,
+        r#"# Flow Efficiency (%)
+set title "Flow Efficiency - Active Time / Cycle Time - %"
+unset key
+set xdata time
+set timefmt "%Y-%m-%d"
+set yrange [0:100]
+plot "{data_file_name}" using 1:5 with linespoints"#,
+        data_file_name = periods_data_file_name
+    )
+    .unwrap();
+    // Time spent in each waiting state
+    writeln!(
+        &mut buffer,
+        r#"# Wait States (Days)
+set title "Time Waiting in State - Days"
+unset key
+set xdata
+set yrange [0:*]
+set style fill solid 0.5
+set boxwidth 0.8
+plot "{data_file_name}" using 1:3:xtic(2) with boxes"#,
+        data_file_name = wait_states_data_file_name
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_flow_efficiency.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_rework(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let rework = &report_project.rework;
+
+    // ---------
+    // Period Rework Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(
+        &mut buffer,
+        "# date moves backward_moves rework_rate_percent"
+    )
+    .unwrap();
+    // record
+    for period_rework in rework.periods.iter() {
+        let date = period_rework.date;
+        writeln!(
+            &mut buffer,
+            "{:04}-{:02}-{:02} {} {} {}",
+            date.year(),
+            date.month(),
+            date.day(),
+            period_rework.moves,
+            period_rework.backward_moves,
+            period_rework
+                .rework_rate
+                .map(|rate| format!("{:.1}", rate * 100.0))
+                .unwrap_or_else(|| String::from("NaN"))
+        )
+        .unwrap();
+    }
+    // data file
+    let data_file_name = format!("{}_rework.dat", label);
+    let mut data_file_path = PathBuf::from(output_dir_path);
+    data_file_path.push(&data_file_name);
+    File::create(&data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", data_file_path.to_str().unwrap());
+
+    // ---------
+    // Backward Transitions CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "from,to,count").unwrap();
+    // record
+    for transition in rework.transitions.iter() {
+        writeln!(
+            &mut buffer,
+            "\"{}\",\"{}\",{}",
+            transition.from.replace('"', "\"\""),
+            transition.to.replace('"', "\"\""),
+            transition.count
+        )
+        .unwrap();
+    }
+    for transition in rework.transitions.iter().take(REWORK_SUMMARY_LENGTH) {
+        println!(
+            "Moved back from {} to {}: {} times",
+            transition.from, transition.to, transition.count
+        );
+    }
+    // csv file
+    let transitions_file_name = format!("{}_backward_transitions.csv", label);
+    let mut transitions_file_path = PathBuf::from(output_dir_path);
+    transitions_file_path.push(&transitions_file_name);
+    File::create(&transitions_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", transitions_file_path.to_str().unwrap());
+
+    // ---------
+    // Rework Tasks CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "gid,name,bounces").unwrap();
+    // record
+    for task in rework.tasks.iter() {
+        writeln!(
+            &mut buffer,
+            "{},\"{}\",{}",
+            task.task_gid,
+            task.name.replace('"', "\"\""),
+            task.bounces
+        )
+        .unwrap();
+    }
+    for task in rework.tasks.iter().take(REWORK_SUMMARY_LENGTH) {
+        println!(
+            "Moved back {} times: {} {}",
+            task.bounces, task.task_gid, task.name
+        );
+    }
+    // csv file
+    let tasks_file_name = format!("{}_rework_tasks.csv", label);
+    let mut tasks_file_path = PathBuf::from(output_dir_path);
+    tasks_file_path.push(&tasks_file_name);
+    File::create(&tasks_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", tasks_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,768
+set output "{label}_rework.png"
+set multiplot layout 2,1 title "{name}""#,
+        label = label,
+        name = name
+    )
+    .unwrap();
+    // Backward moves of each period
+    writeln!(
+        &mut buffer,
+        r#"# Backward Moves (Count)
+set title "Rework - Backward Moves - Count"
+unset key
+set xdata time
+set timefmt "%Y-%m-%d"
+set yrange [0:*]
+set style fill solid 0.5
+plot "{data_file_name}" using 1:3 with boxes"#,
+        data_file_name = data_file_name
+    )
+    .unwrap();
+    // Share of the moves of each period that went backwards
+    writeln!(
+        &mut buffer,
+        r#"# Rework Rate (%)
+set title "Rework Rate - Backward Moves / All Moves - %"
+unset key
+set yrange [0:100]
+plot "{data_file_name}" using 1:4 with linespoints"#,
+        data_file_name = data_file_name
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_rework.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_assignees(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let assignees = &report_project.assignees;
+    let percentiles = &report_project.cycle_times.percentiles;
+
+    if assignees.is_empty() {
+        println!("No tasks in {}, skipping assignees", label);
+        return;
+    }
+    let assignee_name = |assignee: &AssigneeFlow| assignee.name.replace('"', "'");
+
+    // ---------
+    // Assignees Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(
+        &mut buffer,
+        "# idx assignee team wip aging_count alert_count"
+    )
+    .unwrap();
+    write_stats_header(&mut buffer, "cycle", percentiles);
+    writeln!(&mut buffer).unwrap();
+    // record
+    for (idx, assignee) in assignees.iter().enumerate() {
+        let wip = assignee.periods.last().map_or(0, |p| p.wip);
+        write!(
+            &mut buffer,
+            "{} \"{}\" {} {} {} {}",
+            idx,
+            assignee_name(assignee),
+            u8::from(assignee.team),
+            wip,
+            assignee.aging_task_gids.len(),
+            assignee.alert_count
+        )
+        .unwrap();
+        write_stats_days(&mut buffer, &assignee.cycle_time_seconds);
+        writeln!(&mut buffer).unwrap();
+        println!(
+            "{}{}: {} in progress ({} past their alert age), {} delivered",
+            assignee.name,
+            if assignee.team { " (team)" } else { "" },
+            wip,
+            assignee.alert_count,
+            assignee.delivered_count
+        );
+    }
+    // data file
+    let assignees_data_file_name = format!("{}_assignees.dat", label);
+    let mut assignees_data_file_path = PathBuf::from(output_dir_path);
+    assignees_data_file_path.push(&assignees_data_file_name);
+    File::create(&assignees_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", assignees_data_file_path.to_str().unwrap());
+
+    // ---------
+    // WIP and Throughput per Assignee Data Files
+    // ---------
+    let mut wip_buffer = String::new();
+    let mut throughput_buffer = String::new();
+    // header
+    for buffer in [&mut wip_buffer, &mut throughput_buffer] {
+        write!(buffer, "# date").unwrap();
+        for assignee in assignees.iter() {
+            write!(buffer, " \"{}\"", assignee_name(assignee)).unwrap();
+        }
+        writeln!(buffer).unwrap();
+    }
+    // record
+    for (idx, period_flow) in assignees[0].periods.iter().enumerate() {
+        let date = period_flow.date;
+        for buffer in [&mut wip_buffer, &mut throughput_buffer] {
+            write!(
+                buffer,
+                "{:04}-{:02}-{:02}",
+                date.year(),
+                date.month(),
+                date.day()
+            )
+            .unwrap();
+        }
+        for assignee in assignees.iter() {
+            let period_flow = &assignee.periods[idx];
+            write!(&mut wip_buffer, " {}", period_flow.wip).unwrap();
+            write!(&mut throughput_buffer, " {}", period_flow.delivered_count).unwrap();
+        }
+        writeln!(&mut wip_buffer).unwrap();
+        writeln!(&mut throughput_buffer).unwrap();
+    }
+    // data files
+    let wip_data_file_name = format!("{}_assignee_wip.dat", label);
+    let throughput_data_file_name = format!("{}_assignee_throughput.dat", label);
+    for (file_name, buffer) in [
+        (&wip_data_file_name, &wip_buffer),
+        (&throughput_data_file_name, &throughput_buffer),
+    ] {
+        let mut data_file_path = PathBuf::from(output_dir_path);
+        data_file_path.push(file_name);
+        File::create(&data_file_path)
+            .unwrap()
+            .write_all(buffer.as_bytes())
+            .unwrap();
+        println!("Wrote {}", data_file_path.to_str().unwrap());
+    }
+
+    // ---------
+    // Gnuplot
+    // ---------
+    // one line per assignee, teams dashed
+    let make_assignee_lines = |file_name: &str| {
+        assignees
+            .iter()
+            .enumerate()
+            .map(|(idx, assignee)| {
+                format!(
+                    "\"{}\" using 1:{} with linespoints{} title \"{}\"",
+                    file_name,
+                    idx + 2,
+                    if assignee.team { " dt 2" } else { "" },
+                    assignee_name(assignee)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,1024
+set output "{label}_assignees.png"
+set multiplot layout 3,1 title "{name}""#,
+        label = label,
+        name = name
+    )
+    .unwrap();
+    // Work in progress of each assignee over time
+    writeln!(
+        &mut buffer,
+        r#"# WIP per Assignee
+set title "Work in Progress per Assignee - Committed, not Delivered - Count"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+set yrange [0:*]
+plot {lines}"#,
+        lines = make_assignee_lines(&wip_data_file_name)
+    )
+    .unwrap();
+    // Tasks delivered by each assignee per period
+    writeln!(
+        &mut buffer,
+        r#"# Throughput per Assignee
+set title "Throughput per Assignee - Tasks Delivered per Period - Count"
+plot {lines}"#,
+        lines = make_assignee_lines(&throughput_data_file_name)
+    )
+    .unwrap();
+    // Cycle time percentiles of each assignee
+    let percentile_points: Vec<String> = percentiles
+        .iter()
+        .enumerate()
+        .map(|(idx, pct)| {
+            format!(
+                "\"{}\" using 1:{}:xtic(2) with points pt 7 title \"P{}\"",
+                assignees_data_file_name,
+                idx + 7 + STATS_COLUMNS.len(),
+                pct
+            )
+        })
+        .collect();
+    writeln!(
+        &mut buffer,
+        r#"# Cycle Time per Assignee
+set title "Cycle Time per Assignee - Days"
+set xdata
+set xrange [-0.5:{max_idx}.5]
+plot {points}"#,
+        max_idx = assignees.len() - 1,
+        points = percentile_points.join(", ")
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_assignees.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_segments(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let segments = &report_project.segments;
+    let percentiles = &report_project.cycle_times.percentiles;
+
+    if segments.is_empty() {
+        return;
+    }
+    let segment_name = |segment: &Segment| segment.name.replace('"', "'");
+
+    // ---------
+    // Segments Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(&mut buffer, "# idx segment task_count").unwrap();
+    write_stats_header(&mut buffer, "cycle", percentiles);
+    write_stats_header(&mut buffer, "lead", percentiles);
+    writeln!(&mut buffer).unwrap();
+    // record
+    for (idx, segment) in segments.iter().enumerate() {
+        let cycle_times = &segment.cycle_times;
+        write!(
+            &mut buffer,
+            "{} \"{}\" {}",
+            idx,
+            segment_name(segment),
+            segment.task_count
+        )
+        .unwrap();
+        write_stats_days(&mut buffer, &cycle_times.cycle_time_seconds);
+        write_stats_days(&mut buffer, &cycle_times.lead_time_seconds);
+        writeln!(&mut buffer).unwrap();
+        println!(
+            "Segment {}: {} tasks, {} delivered, cycle time P{} {} days",
+            segment.name,
+            segment.task_count,
+            cycle_times.cycle_time_seconds.count,
+            percentiles.last().copied().unwrap_or_default(),
+            cycle_times
+                .cycle_time_seconds
+                .percentiles
+                .last()
+                .copied()
+                .flatten()
+                .map_or(String::from("-"), |seconds| format!(
+                    "{:.1}",
+                    seconds_to_days(seconds)
+                ))
+        );
+    }
+    // data file
+    let segments_data_file_name = format!("{}_segments.dat", label);
+    let mut segments_data_file_path = PathBuf::from(output_dir_path);
+    segments_data_file_path.push(&segments_data_file_name);
+    File::create(&segments_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", segments_data_file_path.to_str().unwrap());
+
+    // ---------
+    // WIP and Throughput per Segment Data Files
+    // ---------
+    let done_states = &report_project.cfd.done_states;
+    let wip_state_idxs: Vec<usize> = report_project
+        .cfd
+        .cfd_states
+        .iter()
+        .enumerate()
+        .filter(|(_, state)| !done_states.contains(state))
+        .map(|(idx, _)| idx)
+        .collect();
+    let mut wip_buffer = String::new();
+    let mut throughput_buffer = String::new();
+    // header
+    for buffer in [&mut wip_buffer, &mut throughput_buffer] {
+        write!(buffer, "# date").unwrap();
+        for segment in segments.iter() {
+            write!(buffer, " \"{}\"", segment_name(segment)).unwrap();
+        }
+        writeln!(buffer).unwrap();
+    }
+    // record
+    for (idx, period_counts) in segments[0].cfd.period_counts.iter().enumerate() {
+        let date = period_counts.date;
+        for buffer in [&mut wip_buffer, &mut throughput_buffer] {
+            write!(
+                buffer,
+                "{:04}-{:02}-{:02}",
+                date.year(),
+                date.month(),
+                date.day()
+            )
+            .unwrap();
+        }
+        for segment in segments.iter() {
+            let period_counts = &segment.cfd.period_counts[idx];
+            let wip: u32 = wip_state_idxs
+                .iter()
+                .map(|&state_idx| period_counts.cfd_state_counts[state_idx])
+                .sum();
+            write!(&mut wip_buffer, " {}", wip).unwrap();
+            write!(&mut throughput_buffer, " {}", period_counts.done_count).unwrap();
+        }
+        writeln!(&mut wip_buffer).unwrap();
+        writeln!(&mut throughput_buffer).unwrap();
+    }
+    // data files
+    let wip_data_file_name = format!("{}_segment_wip.dat", label);
+    let throughput_data_file_name = format!("{}_segment_throughput.dat", label);
+    for (file_name, buffer) in [
+        (&wip_data_file_name, &wip_buffer),
+        (&throughput_data_file_name, &throughput_buffer),
+    ] {
+        let mut data_file_path = PathBuf::from(output_dir_path);
+        data_file_path.push(file_name);
+        File::create(&data_file_path)
+            .unwrap()
+            .write_all(buffer.as_bytes())
+            .unwrap();
+        println!("Wrote {}", data_file_path.to_str().unwrap());
+    }
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let make_segment_lines = |file_name: &str| {
+        segments
+            .iter()
+            .enumerate()
+            .map(|(idx, segment)| {
+                format!(
+                    "\"{}\" using 1:{} with linespoints title \"{}\"",
+                    file_name,
+                    idx + 2,
+                    segment_name(segment)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,1024
+set output "{label}_segments.png"
+set multiplot layout 3,1 title "{name}""#,
+        label = label,
+        name = name
+    )
+    .unwrap();
+    // Tasks not done in each segment over time
+    writeln!(
+        &mut buffer,
+        r#"# WIP per Segment
+set title "Work in Progress per Segment - Tasks not Done - Count"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+set yrange [0:*]
+plot {lines}"#,
+        lines = make_segment_lines(&wip_data_file_name)
+    )
+    .unwrap();
+    // Tasks done in each segment per period
+    writeln!(
+        &mut buffer,
+        r#"# Throughput per Segment
+set title "Throughput per Segment - Tasks Done per Period - Count"
+plot {lines}"#,
+        lines = make_segment_lines(&throughput_data_file_name)
+    )
+    .unwrap();
+    // Cycle time percentiles of each segment
+    let percentile_points: Vec<String> = percentiles
+        .iter()
+        .enumerate()
+        .map(|(idx, pct)| {
+            format!(
+                "\"{}\" using 1:{}:xtic(2) with points pt 7 title \"P{}\"",
+                segments_data_file_name,
+                idx + 4 + STATS_COLUMNS.len(),
+                pct
+            )
+        })
+        .collect();
+    writeln!(
+        &mut buffer,
+        r#"# Cycle Time per Segment
+set title "Cycle Time per Segment - Days"
+set xdata
+set xrange [-0.5:{max_idx}.5]
+plot {points}"#,
+        max_idx = segments.len() - 1,
+        points = percentile_points.join(", ")
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_segments.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn output_transitions(report_project: &Project, output_dir_path: &Path) {
+    let name = report_project.name;
+    let label = report_project.label;
+    let transitions = &report_project.transitions;
+    let median_days = |transition: &Transition| {
+        transition
+            .median_seconds
+            .map(|seconds| format!("{:.1}", seconds_to_days(seconds)))
+    };
+
+    // ---------
+    // Transitions CSV File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "from,to,count,median_days").unwrap();
+    // record
+    for transition in transitions.transitions.iter() {
+        writeln!(
+            &mut buffer,
+            "\"{}\",\"{}\",{},{}",
+            transition.from.replace('"', "\"\""),
+            transition.to.replace('"', "\"\""),
+            transition.count,
+            median_days(transition).unwrap_or_default()
+        )
+        .unwrap();
+    }
+    // csv file
+    let csv_file_name = format!("{}_transitions.csv", label);
+    let mut csv_file_path = PathBuf::from(output_dir_path);
+    csv_file_path.push(&csv_file_name);
+    File::create(&csv_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", csv_file_path.to_str().unwrap());
+
+    // ---------
+    // Graphviz DOT File
+    // ---------
+    let cfd_states = &report_project.cfd.cfd_states;
+    let is_backward = |transition: &Transition| {
+        let from_idx = cfd_states.iter().position(|&s| s == transition.from);
+        let to_idx = cfd_states.iter().position(|&s| s == transition.to);
+        matches!((from_idx, to_idx), (Some(from_idx), Some(to_idx)) if to_idx < from_idx)
+    };
+    let dot_string = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let max_count = transitions
+        .transitions
+        .iter()
+        .map(|t| t.count)
+        .max()
+        .unwrap_or(1);
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"digraph "{label}" {{
+  label="{name}";
+  rankdir=LR;
+  node [shape=box];"#,
+        label = dot_string(label),
+        name = dot_string(name)
+    )
+    .unwrap();
+    for &state in transitions.states.iter() {
+        let style = if transitions.required_states.contains(&state) {
+            " style=bold"
+        } else if !cfd_states.contains(&state) {
+            " style=dashed"
+        } else {
+            ""
+        };
+        writeln!(
+            &mut buffer,
+            "  \"{}\" [{}];",
+            dot_string(state),
+            style.trim()
+        )
+        .unwrap();
+    }
+    // backward moves in red, edges as thick as the share of the busiest one
+    for transition in transitions.transitions.iter() {
+        writeln!(
+            &mut buffer,
+            "  \"{}\" -> \"{}\" [label=\"{}{}\" penwidth={:.1}{}];",
+            dot_string(transition.from),
+            dot_string(transition.to),
+            transition.count,
+            median_days(transition)
+                .map(|days| format!("\\n{} d", days))
+                .unwrap_or_default(),
+            1.0 + 4.0 * f64::from(transition.count) / f64::from(max_count),
+            if is_backward(transition) {
+                " color=red fontcolor=red"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+    writeln!(&mut buffer, "}}").unwrap();
+    // dot file
+    let dot_file_name = format!("{}_transitions.dot", label);
+    let mut dot_file_path = PathBuf::from(output_dir_path);
+    dot_file_path.push(&dot_file_name);
+    File::create(&dot_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", dot_file_path.to_str().unwrap());
+
+    // ---------
+    // Stage Skips CSV File
+    // ---------
+    if transitions.required_states.is_empty() {
+        return;
+    }
+    let mut buffer = String::new();
+    // header
+    writeln!(&mut buffer, "gid,name,at,from,to,skipped_states").unwrap();
+    // record
+    for skip in transitions.skips.iter() {
+        let skipped_states = skip.skipped_states.join(", ");
+        writeln!(
+            &mut buffer,
+            "{},\"{}\",{},\"{}\",\"{}\",\"{}\"",
+            skip.task_gid,
+            skip.name.replace('"', "\"\""),
+            skip.at.to_rfc3339(),
+            skip.from.replace('"', "\"\""),
+            skip.to.replace('"', "\"\""),
+            skipped_states.replace('"', "\"\"")
+        )
+        .unwrap();
+        println!(
+            "Skipped {}: {} {} moved from {} to {} on {}",
+            skipped_states,
+            skip.task_gid,
+            skip.name,
+            skip.from,
+            skip.to,
+            skip.at.date_naive()
+        );
+    }
+    // csv file
+    let skips_file_name = format!("{}_stage_skips.csv", label);
+    let mut skips_file_path = PathBuf::from(output_dir_path);
+    skips_file_path.push(&skips_file_name);
+    File::create(&skips_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", skips_file_path.to_str().unwrap());
+}
+
+fn output_gnuplot_portfolio(portfolio: &Portfolio, output_dir_path: &Path) {
+    let label = portfolio.label;
+    let stages = &portfolio.cfd.cfd_states;
+    let cycle_times = &portfolio.cycle_times;
+
+    println!(
+        "Output for portfolio {}: {} tasks in {}",
+        label,
+        portfolio.task_count,
+        portfolio.projects.join(", ")
+    );
+
+    // ---------
+    // CFD and Done Count Data Files
+    // ---------
+    let mut cfd_buffer = String::new();
+    let mut done_buffer = String::new();
+    // header
+    write!(&mut cfd_buffer, "# date").unwrap();
+    for stage in stages {
+        write!(&mut cfd_buffer, " \"{}\"", stage).unwrap();
+    }
+    writeln!(&mut cfd_buffer).unwrap();
+    writeln!(&mut done_buffer, "# date done_count").unwrap();
+    // record
+    for period_counts in portfolio.cfd.period_counts.iter() {
+        let date = period_counts.date;
+        let date_str = format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day());
+        write!(&mut cfd_buffer, "{}", date_str).unwrap();
+        for count in period_counts.cfd_state_counts.iter() {
+            write!(&mut cfd_buffer, " {}", count).unwrap();
+        }
+        writeln!(&mut cfd_buffer).unwrap();
+        writeln!(
+            &mut done_buffer,
+            "{} {}",
+            date_str, period_counts.done_count
+        )
+        .unwrap();
+    }
+    // data files
+    let cfd_data_file_name = format!("{}_portfolio_cfd.dat", label);
+    let done_count_data_file_name = format!("{}_portfolio_done.dat", label);
+    for (file_name, buffer) in [
+        (&cfd_data_file_name, &cfd_buffer),
+        (&done_count_data_file_name, &done_buffer),
+    ] {
+        let mut data_file_path = PathBuf::from(output_dir_path);
+        data_file_path.push(file_name);
+        File::create(&data_file_path)
+            .unwrap()
+            .write_all(buffer.as_bytes())
+            .unwrap();
+        println!("Wrote {}", data_file_path.to_str().unwrap());
+    }
+
+    // ---------
+    // Cycle and Lead Time Data File
+    // ---------
+    let mut buffer = String::new();
+    // header
+    write!(&mut buffer, "# date").unwrap();
+    write_stats_header(&mut buffer, "cycle", &cycle_times.percentiles);
+    write_stats_header(&mut buffer, "lead", &cycle_times.percentiles);
+    writeln!(&mut buffer).unwrap();
+    // record
+    for period_task_times in cycle_times.periods.iter() {
+        let date = period_task_times.date;
+        write!(
+            &mut buffer,
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month(),
+            date.day()
+        )
+        .unwrap();
+        write_stats_days(&mut buffer, &period_task_times.cycle_time_seconds);
+        write_stats_days(&mut buffer, &period_task_times.lead_time_seconds);
+        writeln!(&mut buffer).unwrap();
+    }
+    // data file
+    let cycle_times_data_file_name = format!("{}_portfolio_cycle_times.dat", label);
+    let mut cycle_times_data_file_path = PathBuf::from(output_dir_path);
+    cycle_times_data_file_path.push(&cycle_times_data_file_name);
+    File::create(&cycle_times_data_file_path)
+        .unwrap()
+        .write_all(buffer.as_bytes())
+        .unwrap();
+    println!("Wrote {}", cycle_times_data_file_path.to_str().unwrap());
+
+    // ---------
+    // Gnuplot
+    // ---------
+    let mut buffer = String::new();
+    writeln!(
+        &mut buffer,
+        r#"
+set terminal png enhanced font "Arial,10" fontscale 1.0 size 1024,1024
+set output "{label}_portfolio.png"
+set multiplot layout 3,1 title "{label}: {projects}""#,
+        label = label.replace('"', "'"),
+        projects = portfolio.projects.join(", ").replace('"', "'")
+    )
+    .unwrap();
+    // CFD - Counts
+    writeln!(
+        &mut buffer,
+        r#"# CFD
+set title "Work in Progress - Tasks in Stage, Stacked - Count"
+set key left top outside
+set xdata time
+set timefmt "%Y-%m-%d"
+{plotline}"#,
+        plotline = make_gnuplot_cfdline(&cfd_data_file_name, stages)
+    )
+    .unwrap();
+    // Task "Done" per period
+    writeln!(
+        &mut buffer,
+        r#"# Tasks "Done" per period
+set title "Throughput - Tasks Transitioning Into {done_stage_names} - Count"
+unset key
+plot "{data_file_name}" using 1:2 with filledcurve x1"#,
+        done_stage_names = portfolio.cfd.done_states.join(", "),
+        data_file_name = done_count_data_file_name
+    )
+    .unwrap();
+    // Cycle time percentiles of the tasks delivered in each period
+    writeln!(
+        &mut buffer,
+        r#"# Cycle Time (Days)
+set title "Cycle Time - {commitment_point} to {delivery_stage_names} - Days"
+set key left top outside
+{plotline}"#,
+        commitment_point = cycle_times.commitment_point,
+        delivery_stage_names = cycle_times.delivery_states.join(", "),
+        plotline =
+            make_gnuplot_cycle_time_line(&cycle_times_data_file_name, &cycle_times.percentiles)
+    )
+    .unwrap();
+
+    // gnuplot file
+    let gnuplot_file_name = format!("{}_portfolio.gnuplot", label);
+    let mut gnuplot_file_path = PathBuf::from(output_dir_path);
+    gnuplot_file_path.push(&gnuplot_file_name);
+    let mut gf = File::create(&gnuplot_file_path).unwrap();
+    gf.write_all(buffer.as_bytes()).unwrap();
+    println!("Wrote {}", gnuplot_file_path.to_str().unwrap());
+}
+
+fn print_unknown_states(report_project: &Project) {
+    for unknown_state in report_project.unknown_states.iter() {
+        println!(
+            "Warning: section \"{}\" of {} ({} moves of {} tasks) is not a configured state; if it was renamed, add it to state_aliases",
+            unknown_state.name,
+            report_project.label,
+            unknown_state.event_count,
+            unknown_state.task_count
+        );
+    }
+}
+
+fn make_gnuplot_cfdline(file_name: &str, states: &Vec<&str>) -> String {
+    let mut buffer = String::from("plot");
+    // gnuplot: columns in data files start from 1
+    // col 1 is the date col; state cols are 2, 3, ... states.len() + 1
+    let max_gnuplot_col = states.len() + 1;
+    for (idx, state) in states.iter().enumerate() {
+        // idx starts from 0
+        if idx > 0 {
+            write!(&mut buffer, ",").unwrap()
+        };
+        let gnuplot_column = idx + 2;
+        write!(
+            &mut buffer,
+            r#" "{file_name}" using 1:({col}) with filledcurve x1 title "{state}""#,
+            file_name = file_name,
+            col = make_col_expression(gnuplot_column as u32, max_gnuplot_col as u32),
+            state = state
+        )
+        .unwrap();
+    }
+    writeln!(&mut buffer).unwrap();
+    buffer
+}
+
+fn make_gnuplot_durations_line(file_name: &str, states: &[&str], percentiles: &[u32]) -> String {
+    let mut buffer = String::from("plot");
+    // col 1 is the date col; then a block of stats columns per state
+    let block_len = STATS_COLUMNS.len() + percentiles.len();
+    for (state_idx, state) in states.iter().enumerate() {
+        for (pct_idx, pct) in percentiles.iter().enumerate() {
+            if state_idx > 0 || pct_idx > 0 {
+                write!(&mut buffer, ",").unwrap()
+            };
+            // one colour per state, one dash type per percentile
+            write!(
+                &mut buffer,
+                r#" "{file_name}" using 1:{col} with lines lc {lc} dt {dt} title "{state} P{pct}""#,
+                file_name = file_name,
+                col = 2 + state_idx * block_len + STATS_COLUMNS.len() + pct_idx,
+                lc = state_idx + 1,
+                dt = pct_idx + 1,
+                state = state,
+                pct = pct
+            )
+            .unwrap();
+        }
+    }
+    writeln!(&mut buffer).unwrap();
+    buffer
+}
+
+fn make_gnuplot_cycle_time_line(file_name: &str, percentiles: &[u32]) -> String {
+    let mut buffer = String::from("plot");
+    // col 1 is the date col; the cycle time stats cols follow
+    for (idx, pct) in percentiles.iter().enumerate() {
+        if idx > 0 {
+            write!(&mut buffer, ",").unwrap()
+        };
+        write!(
+            &mut buffer,
+            r#" "{file_name}" using 1:{col} with linespoints title "P{pct}""#,
+            file_name = file_name,
+            col = 2 + STATS_COLUMNS.len() + idx,
+            pct = pct
+        )
+        .unwrap();
+    }
+    writeln!(&mut buffer).unwrap();
+    buffer
+}
+
+// how many backward transitions and reworked tasks are printed
+const REWORK_SUMMARY_LENGTH: usize = 5;
+
+// the columns written by `write_stats_days` before the percentiles
+const STATS_COLUMNS: [&str; 4] = ["count", "min", "max", "mean"];
+
+// writes the headers of the columns of `write_stats_days`, e.g. `"Doing count"`
+fn write_stats_header(buffer: &mut String, prefix: &str, percentiles: &[u32]) {
+    for column in STATS_COLUMNS.iter() {
+        write!(buffer, " \"{} {}\"", prefix, column).unwrap();
+    }
+    for pct in percentiles {
+        write!(buffer, " \"{} P{}\"", prefix, pct).unwrap();
+    }
+}
+
+// writes the count, then the other stats in days; gnuplot skips NaN points
+fn write_stats_days(buffer: &mut String, stats: &Stats) {
+    write!(buffer, " {}", stats.count).unwrap();
+    let values = [stats.min, stats.max, stats.mean];
+    for value in values.iter().chain(stats.percentiles.iter()) {
+        match value {
+            Some(seconds) => write!(buffer, " {}", seconds_to_days(*seconds)).unwrap(),
+            None => write!(buffer, " NaN").unwrap(),
+        }
+    }
+}
+
+fn seconds_to_days(seconds: u64) -> f32 {
+    (seconds as f32) / (24.0 * 60.0 * 60.0)
 }
 
 fn make_col_expression(cur_col: u32, max_col: u32) -> String {
@@ -671,5 +2210,5 @@ fn make_col_expression(cur_col: u32, max_col: u32) -> String {
         };
         write!(&mut buffer, "${}", i).unwrap();
     }
-    return buffer;
+    buffer
 }
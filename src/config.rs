@@ -1,21 +1,191 @@
+use crate::period::Period;
+use crate::token::TokenSource;
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
 pub struct MyConfig {
+    #[serde(default)]
+    pub token: Option<TokenSource>,
+    // named credential profiles, for projects that need a token other than `token`
+    #[serde(default)]
+    pub credentials: HashMap<String, TokenSource>,
+    #[serde(default)]
+    pub http: MyHttpConfig,
+    // regexes for the text of "section_changed" stories, tried before the built-in
+    // English template; each needs the named groups `from`, `to` and `project`.
+    // Accounts in another language need one for the text of their stories
+    #[serde(default)]
+    pub section_changed_patterns: Vec<String>,
+    // team name => emails or gids of its members, for per-team metrics
+    #[serde(default)]
+    pub teams: HashMap<String, Vec<String>>,
     pub projects: HashMap<String, MyProjectConfig>,
+    // portfolio name => projects reported on together; none if absent
+    #[serde(default)]
+    pub portfolios: HashMap<String, MyPortfolioConfig>,
+}
+
+// connection settings for the Asana client
+#[derive(Debug, Default, Deserialize)]
+pub struct MyHttpConfig {
+    // overrides $HTTPS_PROXY
+    #[serde(default)]
+    pub proxy: Option<String>,
+    // overrides $NO_PROXY
+    #[serde(default)]
+    pub no_proxy: Option<Vec<String>>,
+    // PEM files with extra CA certificates to trust
+    #[serde(default)]
+    pub ca_certs: Vec<PathBuf>,
+    #[serde(default)]
+    pub client_identity: Option<MyClientIdentityConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MyClientIdentityConfig {
+    Pkcs12 {
+        pkcs12: PathBuf,
+        #[serde(default)]
+        password: Option<TokenSource>,
+    },
+    Pem {
+        cert: PathBuf,
+        key: PathBuf,
+    },
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MyProjectConfig {
     pub gid: String,
+    // name of an entry in `MyConfig.credentials`; the default token is used if absent
+    #[serde(default)]
+    pub credentials: Option<String>,
     pub horizon: DateTime<Utc>,
+    // end of the report window; now if absent
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    // reporting period; weeks starting on Monday if absent
+    #[serde(default)]
+    pub period: Period,
     pub cfd_states: Vec<String>,
     pub done_states: Vec<String>,
+    // old section name => the state it is now, for renamed sections; none if absent
+    #[serde(default)]
+    pub state_aliases: HashMap<String, String>,
+    // state => most tasks that should be in it at once; no limits if absent
+    #[serde(default)]
+    pub wip_limits: HashMap<String, u32>,
+    // cycle time starts when a task first enters this state or a later one in
+    // `cfd_states`; the first of `cfd_states` if absent
+    #[serde(default)]
+    pub commitment_point: Option<String>,
+    // tasks are delivered when they enter this state or a later one in `cfd_states`;
+    // any of `done_states` if absent
+    #[serde(default)]
+    pub delivery_point: Option<String>,
+    // states in which tasks wait rather than being worked on; all states are active
+    // if absent
+    #[serde(default)]
+    pub waiting_states: Vec<String>,
+    // states tasks must enter before moving to a later one in `cfd_states`; none if
+    // absent
+    #[serde(default)]
+    pub required_states: Vec<String>,
+    // periods in the rolling averages of arrivals and departures; 4 if absent
+    #[serde(default = "default_rolling_average_periods")]
+    pub rolling_average_periods: u32,
+    // percentiles reported for durations, cycle times and aging; 50, 85 and 95 if absent
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<u32>,
+    // how percentiles between two values are computed; the lower value if absent
+    #[serde(default)]
+    pub percentile_interpolation: Interpolation,
+    // segment name => the tasks in it, e.g. a class of service; none if absent
+    #[serde(default)]
+    pub segments: HashMap<String, MySegmentConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MyPortfolioConfig {
+    // the projects in the portfolio; a task in several of them counts in the first
+    pub projects: Vec<MyPortfolioProjectConfig>,
+    // the shared states the sections of the projects map onto, earliest first
+    pub stages: Vec<String>,
+    pub done_stages: Vec<String>,
+    pub horizon: DateTime<Utc>,
+    // end of the report window; now if absent
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    // reporting period; weeks starting on Monday if absent
+    #[serde(default)]
+    pub period: Period,
+    // percentiles reported for durations and cycle times; 50, 85 and 95 if absent
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<u32>,
+    // how percentiles between two values are computed; the lower value if absent
+    #[serde(default)]
+    pub percentile_interpolation: Interpolation,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MyPortfolioProjectConfig {
+    // key of the project in `MyConfig.projects`
+    pub project: String,
+    // section name => stage; sections named like a stage map to it if absent
+    #[serde(default)]
+    pub stage_map: HashMap<String, String>,
+}
+
+// the tasks of a project that belong to a segment
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MySegmentConfig {
+    // tasks with a tag of this name
+    Tag { tag: String },
+    // tasks whose custom field of this name shows this value
+    CustomField { custom_field: String, value: String },
+    // tasks whose name matches this regex
+    NameRegex { name_regex: String },
+}
+
+/// Periods in the rolling averages when a project does not set `rolling_average_periods`.
+pub const DEFAULT_ROLLING_AVERAGE_PERIODS: u32 = 4;
+
+/// Percentiles reported when a project does not set `percentiles`.
+pub const DEFAULT_PERCENTILES: [u32; 3] = [50, 85, 95];
+
+/// How a percentile that falls between two values is computed. With `n` sorted
+/// values, the `p`th percentile is at rank `r = (n - 1) * p / 100`, and is:
+///
+/// - `"lower"`: the value at rank `floor(r)` (the default)
+/// - `"higher"`: the value at rank `ceil(r)`
+/// - `"nearest"`: the value at the rank nearest to `r`
+/// - `"midpoint"`: the mean of the lower and higher values
+/// - `"linear"`: interpolated linearly between the lower and higher values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Lower,
+    Higher,
+    Nearest,
+    Midpoint,
+    Linear,
+}
+
+fn default_rolling_average_periods() -> u32 {
+    DEFAULT_ROLLING_AVERAGE_PERIODS
+}
+
+fn default_percentiles() -> Vec<u32> {
+    DEFAULT_PERCENTILES.to_vec()
 }
 
 pub fn parse_config(config_str: &str) -> MyConfig {
     let config: MyConfig = serde_json::from_str(config_str).expect("Invalid config");
-    return config;
+    config
 }
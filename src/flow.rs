@@ -0,0 +1,802 @@
+//! The flow-metrics engine: turns the data downloaded by `fetch` into per-project,
+//! per-period counts and durations of tasks in each Kanban state.
+
+use crate::asana::*;
+use crate::config::*;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+mod aging;
+mod aliases;
+mod arrivals;
+mod assignees;
+mod cumulative;
+mod cycle_time;
+mod efficiency;
+mod forecast;
+mod portfolio;
+mod rework;
+mod segments;
+mod stats;
+mod stories;
+mod transitions;
+mod wip_limits;
+pub use aging::{get_aging_wip, AgingState, AgingTask, AgingWip, AGING_WIP_ALERT_PERCENTILE};
+pub use aliases::{apply_state_aliases, get_unknown_states, UnknownState};
+pub use arrivals::{get_flow_rates, FlowRates, PeriodFlow};
+pub use assignees::{
+    get_assignee_flows, get_assignee_groups, get_teams, AssigneeFlow, AssigneeGroup,
+    PeriodAssigneeFlow, UNASSIGNED,
+};
+pub use cumulative::{get_cumulative_counts, PeriodCumulativeCounts};
+pub use cycle_time::{
+    get_commitment_delivery_states, get_period_task_times, get_task_time_stats, get_task_times,
+    CycleTimes, PeriodTaskTimes, TaskTimes,
+};
+pub use efficiency::{
+    get_flow_efficiency, FlowEfficiency, PeriodEfficiency, TaskEfficiency, WaitStateTime,
+};
+pub use forecast::{
+    forecast_completion_dates, forecast_item_counts, get_throughput_history, CountForecast,
+    DateForecast, Outcome, FORECAST_CONFIDENCE_LEVELS,
+};
+pub use portfolio::{get_portfolio_events, Portfolio};
+pub use rework::{get_rework, BackwardTransition, PeriodRework, Rework, TaskRework};
+pub use segments::{get_segment_tgids, Segment};
+pub use stats::{percentile, validate_percentiles, Percentiles, Stats};
+pub use stories::{
+    StoryParser, StoryParsing, StoryProject, TemplateCount, UnparsedStory, UnresolvedProject,
+    SECTION_CHANGED_TEMPLATES,
+};
+pub use transitions::{get_required_states, get_transitions, StageSkip, Transition, Transitions};
+pub use wip_limits::{get_wip_breaches, get_wip_limits, WipBreach, WipLimit};
+
+/// A task entering a state: (event time, task gid, state).
+pub type TaskEvent<'a> = (&'a DateTime<Utc>, &'a str, &'a str);
+
+/// The metrics for every project in the config.
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub projects: Vec<Project<'a>>,
+    /// The portfolios of `MyConfig.portfolios`, by label.
+    pub portfolios: Vec<Portfolio<'a>>,
+    pub story_parsing: StoryParsing<'a>,
+}
+
+/// The metrics of one project.
+#[derive(Debug, Serialize)]
+pub struct Project<'a> {
+    /// The key of the project in `MyConfig.projects`.
+    pub label: &'a str,
+    /// The name of the project in Asana.
+    pub name: &'a str,
+    /// Start of the first period.
+    pub start: DateTime<Utc>,
+    /// End of the report; events from then on are ignored.
+    pub end: DateTime<Utc>,
+    pub cfd: Cfd<'a>,
+    pub flow_rates: FlowRates,
+    pub rework: Rework<'a>,
+    pub transitions: Transitions<'a>,
+    pub cycle_times: CycleTimes<'a>,
+    pub aging_wip: AgingWip<'a>,
+    pub flow_efficiency: FlowEfficiency<'a>,
+    /// Each user with tasks, then each team of `MyConfig.teams`.
+    pub assignees: Vec<AssigneeFlow<'a>>,
+    /// The states with a WIP limit, in the order of `Cfd.cfd_states`.
+    pub wip_limits: Vec<WipLimit<'a>>,
+    /// The segments of `MyProjectConfig.segments`, by name.
+    pub segments: Vec<Segment<'a>>,
+    /// The section names in the events, after `MyProjectConfig.state_aliases`, that
+    /// are not one of the states of the project config.
+    pub unknown_states: Vec<UnknownState<'a>>,
+}
+
+/// Per-period series for a project's Cumulative Flow Diagram and throughput.
+/// Values in `period_counts`, `period_durations` and `period_cumulative_counts` are in
+/// the order of `cfd_states`.
+#[derive(Debug, Serialize)]
+pub struct Cfd<'a> {
+    pub cfd_states: Vec<&'a str>,
+    pub done_states: Vec<&'a str>,
+    /// The percentiles in the `Stats` of `period_durations`.
+    pub percentiles: Vec<u32>,
+    /// Tasks in each state: the stacked work in progress.
+    pub period_counts: Vec<PeriodCounts>,
+    pub period_durations: Vec<PeriodDurations>,
+    /// Cumulative arrivals into each state: the textbook cumulative flow diagram.
+    pub period_cumulative_counts: Vec<PeriodCumulativeCounts>,
+}
+
+/// Task counts at the end of the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodCounts {
+    pub date: NaiveDate,
+    /// How many tasks were in each of `Cfd.cfd_states`.
+    pub cfd_state_counts: Vec<u32>,
+    /// How many tasks moved into one of `Cfd.done_states` during the period.
+    pub done_count: u32,
+}
+
+/// Time spent by tasks in each state during the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodDurations {
+    pub date: NaiveDate,
+    /// The time, within the period, tasks spent in each of `Cfd.cfd_states`.
+    pub duration_seconds: Vec<Stats>,
+}
+
+/// Computes the metrics of every project in `config` from the data fetched from Asana.
+/// Periods are those of the project's `period`, from the one containing its `horizon`
+/// up to the one containing its `end` (now, if not set). Events before the first
+/// period only set the state the tasks start it in.
+pub fn proc_data<'a>(config: &'a MyConfig, asana_data: &'a AsanaData) -> Report<'a> {
+    let pgid2pname: HashMap<&str, &str> = get_pgid2pname(asana_data);
+    let sgid2sname: HashMap<&str, &str> = get_sgid2sname(asana_data);
+    let tgid2asana_task: HashMap<&str, &AsanaTask> = get_tgid2asana_task(asana_data);
+    let ugid2uname: HashMap<&str, &str> = get_ugid2uname(asana_data);
+    let sgid2pgid: HashMap<&str, &str> = get_sgid2pgid(asana_data);
+    let tgid2pgid2sname: HashMap<&str, HashMap<&str, &str>> =
+        get_tgid2pgid2sname(&sgid2pgid, &sgid2sname, asana_data);
+
+    // capture the times when a task entered a state ("section")
+    // project gid => Vec<(event_time, task gid, state)>
+    let mut story_parser = StoryParser::new(&config.section_changed_patterns);
+    let pname2pgids: HashMap<&str, Vec<&str>> = get_pname2pgids(&pgid2pname);
+    let pgid2t_events: HashMap<&str, Vec<TaskEvent>> = get_task_events(
+        &tgid2asana_task,
+        &tgid2pgid2sname,
+        &asana_data.task_stories,
+        &pname2pgids,
+        &sgid2pgid,
+        &mut story_parser,
+    );
+    let story_parsing = story_parser.finish();
+
+    let teams: Vec<(&str, Vec<&str>)> = get_teams(config, asana_data);
+
+    let mut projects: Vec<Project> = Vec::new();
+
+    for (label, project_config) in &config.projects {
+        log::info!("Processing: {}", label);
+        let pgid = project_config.gid.as_str();
+        let pname: &str = pgid2pname.get(pgid).copied().unwrap_or_else(|| {
+            panic!(
+                "Project {} of {} is not in the data; fetch it again",
+                pgid, label
+            )
+        });
+        let cfd_states: Vec<&str> = project_config
+            .cfd_states
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        let done_states: Vec<&str> = project_config
+            .done_states
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        let period = &project_config.period;
+        period.validate();
+        validate_percentiles(&project_config.percentiles);
+        if project_config.rolling_average_periods == 0 {
+            panic!("rolling_average_periods must be at least 1");
+        }
+        let percentiles = Percentiles {
+            percentiles: &project_config.percentiles,
+            interpolation: project_config.percentile_interpolation,
+        };
+        let start = period.start_of(&project_config.horizon);
+        let end = project_config.end.unwrap_or_else(Utc::now);
+        let events: Vec<TaskEvent> = apply_state_aliases(
+            pgid2t_events.get(pgid).map_or(&[], |events| &events[..]),
+            &project_config.state_aliases,
+        );
+        // events are sorted by time
+        let events = &events[..events.partition_point(|event| *event.0 < end)];
+        let known_states: Vec<&str> = project_config
+            .cfd_states
+            .iter()
+            .chain(&project_config.done_states)
+            .chain(&project_config.waiting_states)
+            .map(|s| s.as_str())
+            .collect();
+        let unknown_states = get_unknown_states(events, &known_states);
+        for unknown_state in &unknown_states {
+            log::warn!(
+                "Section {} of {} is not a configured state",
+                unknown_state.name,
+                label
+            );
+        }
+
+        let cfd = get_cfd(
+            events,
+            &cfd_states,
+            &done_states,
+            period,
+            &percentiles,
+            &start,
+            &end,
+        );
+
+        let flow_rates = get_flow_rates(
+            events,
+            &cfd_states,
+            &done_states,
+            period,
+            &start,
+            &end,
+            project_config.rolling_average_periods,
+        );
+
+        let rework = get_rework(
+            events,
+            &tgid2asana_task,
+            &cfd_states,
+            &done_states,
+            period,
+            &start,
+            &end,
+        );
+
+        let transitions = get_transitions(
+            events,
+            &tgid2asana_task,
+            &cfd_states,
+            &get_required_states(project_config),
+            project_config.percentile_interpolation,
+            &start,
+        );
+
+        let (commitment_point, committed_states, delivery_states) =
+            get_commitment_delivery_states(project_config);
+        let task_times = get_task_times(
+            events,
+            &tgid2asana_task,
+            &committed_states,
+            &delivery_states,
+            &start,
+        );
+        let cycle_times = get_cycle_times(
+            task_times,
+            commitment_point,
+            &delivery_states,
+            period,
+            &percentiles,
+            &start,
+            &end,
+        );
+        let aging_wip = get_aging_wip(
+            events,
+            &tgid2asana_task,
+            &ugid2uname,
+            &committed_states,
+            &delivery_states,
+            &end,
+            &percentiles,
+        );
+        let waiting_states: Vec<&str> = project_config
+            .waiting_states
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        let flow_efficiency = get_flow_efficiency(
+            events,
+            &cycle_times.tasks,
+            &waiting_states,
+            period,
+            &start,
+            &end,
+        );
+        let assignee_groups =
+            get_assignee_groups(&cycle_times.tasks, &tgid2asana_task, &ugid2uname, &teams);
+        let assignees = get_assignee_flows(
+            &assignee_groups,
+            &cycle_times.tasks,
+            &aging_wip,
+            period,
+            &start,
+            &end,
+            &percentiles,
+        );
+        let wip_limits = get_wip_breaches(events, &get_wip_limits(project_config), &start, &end);
+
+        let mut segment_configs: Vec<(&String, &MySegmentConfig)> =
+            project_config.segments.iter().collect();
+        segment_configs.sort_by_key(|&(name, _)| name);
+        let mut segments: Vec<Segment> = Vec::new();
+        for (segment_name, segment_config) in segment_configs {
+            let tgids = get_segment_tgids(events, &tgid2asana_task, segment_config);
+            let segment_events: Vec<TaskEvent> = events
+                .iter()
+                .filter(|&&(_, tgid, _)| tgids.contains(tgid))
+                .copied()
+                .collect();
+            let segment_task_times = get_task_times(
+                &segment_events,
+                &tgid2asana_task,
+                &committed_states,
+                &delivery_states,
+                &start,
+            );
+            segments.push(Segment {
+                name: segment_name,
+                task_count: tgids.len() as u32,
+                cfd: get_cfd(
+                    &segment_events,
+                    &cfd_states,
+                    &done_states,
+                    period,
+                    &percentiles,
+                    &start,
+                    &end,
+                ),
+                cycle_times: get_cycle_times(
+                    segment_task_times,
+                    commitment_point,
+                    &delivery_states,
+                    period,
+                    &percentiles,
+                    &start,
+                    &end,
+                ),
+            });
+        }
+
+        let project = Project {
+            label,
+            name: pname,
+            start,
+            end,
+            cfd,
+            flow_rates,
+            rework,
+            transitions,
+            cycle_times,
+            aging_wip,
+            flow_efficiency,
+            assignees,
+            wip_limits,
+            segments,
+            unknown_states,
+        };
+        projects.push(project);
+    }
+
+    let mut portfolio_labels: Vec<&String> = config.portfolios.keys().collect();
+    portfolio_labels.sort();
+    let mut portfolios: Vec<Portfolio> = Vec::new();
+    for label in portfolio_labels {
+        log::info!("Processing portfolio: {}", label);
+        let portfolio_config = &config.portfolios[label];
+        let stages: Vec<&str> = portfolio_config.stages.iter().map(|s| s.as_str()).collect();
+        let done_stages: Vec<&str> = portfolio_config
+            .done_stages
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        let period = &portfolio_config.period;
+        period.validate();
+        validate_percentiles(&portfolio_config.percentiles);
+        let percentiles = Percentiles {
+            percentiles: &portfolio_config.percentiles,
+            interpolation: portfolio_config.percentile_interpolation,
+        };
+        let start = period.start_of(&portfolio_config.horizon);
+        let end = portfolio_config.end.unwrap_or_else(Utc::now);
+        let events: Vec<TaskEvent> = get_portfolio_events(config, portfolio_config, &pgid2t_events);
+        // events are sorted by time
+        let events = &events[..events.partition_point(|event| *event.0 < end)];
+
+        // committed from the first stage, as for projects without a commitment point
+        let commitment_point = *stages.first().expect("stages must not be empty");
+        let task_times = get_task_times(events, &tgid2asana_task, &stages, &done_stages, &start);
+        let task_count = events
+            .iter()
+            .map(|&(_, tgid, _)| tgid)
+            .collect::<HashSet<&str>>()
+            .len() as u32;
+
+        portfolios.push(Portfolio {
+            label,
+            projects: portfolio_config
+                .projects
+                .iter()
+                .map(|p| p.project.as_str())
+                .collect(),
+            start,
+            end,
+            task_count,
+            cfd: get_cfd(
+                events,
+                &stages,
+                &done_stages,
+                period,
+                &percentiles,
+                &start,
+                &end,
+            ),
+            cycle_times: get_cycle_times(
+                task_times,
+                commitment_point,
+                &done_stages,
+                period,
+                &percentiles,
+                &start,
+                &end,
+            ),
+        });
+    }
+
+    Report {
+        projects,
+        portfolios,
+        story_parsing,
+    }
+}
+
+// the CFD series of `events`, sorted by time and ending at `end`
+fn get_cfd<'a>(
+    events: &[TaskEvent<'a>],
+    cfd_states: &[&'a str],
+    done_states: &[&'a str],
+    period: &Period,
+    percentiles: &Percentiles,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Cfd<'a> {
+    let mut builder = CfdBuilder::new(period, cfd_states, done_states, percentiles, *start);
+    for event in events {
+        builder.add_event(event);
+    }
+    let (period_counts, period_durations) = builder.finish(end);
+    Cfd {
+        cfd_states: cfd_states.to_vec(),
+        done_states: done_states.to_vec(),
+        percentiles: percentiles.percentiles.to_vec(),
+        period_counts,
+        period_durations,
+        period_cumulative_counts: get_cumulative_counts(events, cfd_states, period, start, end),
+    }
+}
+
+// the cycle and lead times of `task_times`, overall and per period
+fn get_cycle_times<'a>(
+    task_times: Vec<TaskTimes<'a>>,
+    commitment_point: &'a str,
+    delivery_states: &[&'a str],
+    period: &Period,
+    percentiles: &Percentiles,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> CycleTimes<'a> {
+    let periods = get_period_task_times(&task_times, period, start, end, percentiles);
+    let (cycle_time_seconds, lead_time_seconds) = get_task_time_stats(&task_times, percentiles);
+    CycleTimes {
+        commitment_point,
+        delivery_states: delivery_states.to_vec(),
+        percentiles: percentiles.percentiles.to_vec(),
+        cycle_time_seconds,
+        lead_time_seconds,
+        tasks: task_times,
+        periods,
+    }
+}
+
+// Replays a project's events in time order, closing a period whenever an event
+// falls after its end.
+struct CfdBuilder<'a, 'b> {
+    period: &'b Period,
+    cfd_states: &'b [&'a str],
+    done_states: &'b [&'a str],
+    percentiles: &'b Percentiles<'b>,
+    // start of the first period; earlier events only set the state of tasks
+    start: DateTime<Utc>,
+    start_of_period: DateTime<Utc>,
+    start_of_next_period: DateTime<Utc>,
+    // last know state of each task, and the timestamp when task entered that state
+    task_latest_state: HashMap<&'a str, (&'a str, &'a DateTime<Utc>)>,
+    // *in this period* how much time did tasks spend in this state
+    state_period_dwelltimes: HashMap<&'a str, Vec<u64>>,
+    // *in this period* how many tasks are in states considered to be "Done"
+    // note - there can be multiple states that are considered to conceptually
+    // be Done
+    done_count: u32,
+    period_counts: Vec<PeriodCounts>,
+    period_durations: Vec<PeriodDurations>,
+}
+
+impl<'a, 'b> CfdBuilder<'a, 'b> {
+    fn new(
+        period: &'b Period,
+        cfd_states: &'b [&'a str],
+        done_states: &'b [&'a str],
+        percentiles: &'b Percentiles<'b>,
+        start: DateTime<Utc>,
+    ) -> Self {
+        CfdBuilder {
+            period,
+            cfd_states,
+            done_states,
+            percentiles,
+            start,
+            start_of_period: start,
+            start_of_next_period: period.next(&start),
+            task_latest_state: HashMap::new(),
+            state_period_dwelltimes: HashMap::new(),
+            done_count: 0,
+            period_counts: Vec::new(),
+            period_durations: Vec::new(),
+        }
+    }
+
+    fn add_event(&mut self, &(at, task_gid, sname): &TaskEvent<'a>) {
+        while at >= &self.start_of_next_period {
+            // event in next period -- finalize this period stats and rollover to next period
+            let end_of_period = self.start_of_next_period;
+            self.close_period(&end_of_period);
+        }
+        let old = self.task_latest_state.insert(task_gid, (sname, at));
+        if at < &self.start {
+            // before the window: only carry the task's state over to the first period
+            return;
+        }
+        // event in current period
+        if let Some((old_state, old_at)) = old {
+            let old_state_duration_seconds = (*at - *old_at).num_seconds() as u64;
+            self.state_period_dwelltimes
+                .entry(old_state)
+                .or_default()
+                .push(old_state_duration_seconds);
+        }
+        if self.done_states.contains(&sname) {
+            self.done_count += 1;
+        }
+    }
+
+    // closes every remaining period that starts before `end`; the last one may be partial
+    fn finish(mut self, end: &DateTime<Utc>) -> (Vec<PeriodCounts>, Vec<PeriodDurations>) {
+        while self.start_of_period < *end {
+            let as_of = std::cmp::min(self.start_of_next_period, *end);
+            self.close_period(&as_of);
+        }
+        (self.period_counts, self.period_durations)
+    }
+
+    // records the stats of the current period as of `as_of`, and moves to the next one
+    fn close_period(&mut self, as_of: &DateTime<Utc>) {
+        // how many tasks are in each state at the moment
+        let mut state_taskcounts: HashMap<&str, u32> = HashMap::new();
+        for (sname, &timestamp) in self.task_latest_state.values() {
+            *state_taskcounts.entry(sname).or_insert(0) += 1;
+
+            let dwelltime = (*as_of - timestamp).num_seconds() as u64;
+            self.state_period_dwelltimes
+                .entry(sname)
+                .or_default()
+                .push(dwelltime);
+        }
+        // extract the counts of the subset of states in `cfd_states`
+        let state_count_vec: Vec<u32> = self
+            .cfd_states
+            .iter()
+            .map(|&k| *state_taskcounts.get(k).unwrap_or(&0))
+            .collect();
+        self.period_counts.push(PeriodCounts {
+            date: self.start_of_period.date_naive(),
+            cfd_state_counts: state_count_vec,
+            done_count: self.done_count,
+        });
+
+        // extract the duration stats of the subsets of states in `cfd_states`
+        let state_period_dwelltimes = &mut self.state_period_dwelltimes;
+        let percentiles = self.percentiles;
+        let duration_seconds: Vec<Stats> = self
+            .cfd_states
+            .iter()
+            .map(|&k| {
+                let dwelltimes = state_period_dwelltimes.entry(k).or_default();
+                Stats::new(dwelltimes, percentiles)
+            })
+            .collect();
+        self.period_durations.push(PeriodDurations {
+            date: self.start_of_period.date_naive(),
+            duration_seconds,
+        });
+
+        // clear the state_durations because we only count the time
+        // tasks spend in a state within a period
+        self.state_period_dwelltimes.clear();
+
+        // reset done_count because we only count tasks done
+        // within this period
+        self.done_count = 0;
+
+        // update loop variables for next period
+        self.start_of_period = self.start_of_next_period;
+        self.start_of_next_period = self.period.next(&self.start_of_period);
+    }
+}
+
+fn get_pgid2pname(asana_data: &AsanaData) -> HashMap<&str, &str> {
+    asana_data
+        .projects
+        .iter()
+        .map(|AsanaProject { gid, name, .. }| (gid.as_str(), name.as_str()))
+        .collect()
+}
+
+fn get_sgid2sname(asana_data: &AsanaData) -> HashMap<&str, &str> {
+    asana_data
+        .project_sections
+        .iter()
+        .flat_map(|aps| {
+            aps.sections
+                .iter()
+                .map(|a_s| (a_s.gid.as_str(), a_s.name.as_str()))
+        })
+        .collect()
+}
+
+fn get_tgid2asana_task(asana_data: &AsanaData) -> HashMap<&str, &AsanaTask> {
+    asana_data
+        .tasks
+        .iter()
+        .map(|t| (t.gid.as_str(), t))
+        .collect()
+}
+
+fn get_ugid2uname(asana_data: &AsanaData) -> HashMap<&str, &str> {
+    asana_data
+        .users
+        .iter()
+        .map(|u| (u.gid.as_str(), u.name.as_str()))
+        .collect()
+}
+
+fn get_sgid2pgid(asana_data: &AsanaData) -> HashMap<&str, &str> {
+    asana_data
+        .project_sections
+        .iter()
+        .flat_map(|aps| {
+            aps.sections
+                .iter()
+                .map(move |a_s| (a_s.gid.as_str(), aps.project_gid.as_str()))
+        })
+        .collect()
+}
+
+fn get_tgid2pgid2sname<'a>(
+    sgid2pgid: &HashMap<&'a str, &'a str>,
+    sgid2sname: &HashMap<&'a str, &'a str>,
+    asana_data: &'a AsanaData,
+) -> HashMap<&'a str, HashMap<&'a str, &'a str>> {
+    asana_data
+        .tasks
+        .iter()
+        .map(|a_t| {
+            (
+                a_t.gid.as_str(),
+                a_t.memberships
+                    .iter()
+                    .map(|hm| hm["section"].gid.as_str())
+                    // AsanaTask.membership lists sections from *all* projects a task is in
+                    // not just the ones we are interested in, so filter out the sections
+                    // that con't exist in our `project_sections`
+                    .filter(|sgid| sgid2pgid.contains_key(*sgid))
+                    .map(|sgid| (sgid2pgid[sgid], sgid2sname[sgid]))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+// The gids of the projects in the data by their current name.
+fn get_pname2pgids<'a>(pgid2pname: &HashMap<&'a str, &'a str>) -> HashMap<&'a str, Vec<&'a str>> {
+    let mut pname2pgids: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (&pgid, &pname) in pgid2pname {
+        pname2pgids.entry(pname).or_default().push(pgid);
+    }
+    pname2pgids
+}
+
+// Splits events sorted by time into the events of each task, and returns the
+// task gids in the order of their first event.
+fn group_events_by_task<'a, 'b>(
+    events: &'b [TaskEvent<'a>],
+) -> (Vec<&'a str>, HashMap<&'a str, Vec<&'b TaskEvent<'a>>>) {
+    let mut tgid2events: HashMap<&str, Vec<&TaskEvent>> = HashMap::new();
+    let mut tgids: Vec<&str> = Vec::new();
+    for event in events {
+        let task_events = tgid2events.entry(event.1).or_default();
+        if task_events.is_empty() {
+            tgids.push(event.1);
+        }
+        task_events.push(event);
+    }
+    (tgids, tgid2events)
+}
+
+/// Converts the "section changed" stories of tasks into a timeline of events per
+/// project gid, sorted by time. A story without section gids is matched by the
+/// project name in its text, to the project of that name the task is in. Stories
+/// that `story_parser` cannot read, or whose project is not one project of the data
+/// the task is in, are skipped and recorded by `story_parser`.
+fn get_task_events<'a>(
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    tgid2pgid2sname: &HashMap<&str, HashMap<&'a str, &'a str>>,
+    task_stories: &'a [AsanaTaskStories],
+    pname2pgids: &HashMap<&str, Vec<&'a str>>,
+    sgid2pgid: &HashMap<&str, &'a str>,
+    story_parser: &mut StoryParser<'a>,
+) -> HashMap<&'a str, Vec<TaskEvent<'a>>> {
+    let mut pgid2t_events: HashMap<&str, Vec<TaskEvent>> = HashMap::new();
+
+    // read all the stories and convert them into a timeline of events per project
+    for asana_task_story in task_stories {
+        let task_gid: &str = asana_task_story.task_gid.as_str();
+        let task_created_at = &tgid2asana_task[task_gid].created_at;
+        let task_pgid2sname = &tgid2pgid2sname[task_gid];
+        // projects in which this task has had an event so far
+        let mut task_pgids: HashSet<&str> = HashSet::new();
+
+        for asana_story in &asana_task_story.stories {
+            if !asana_story.resource_subtype.eq("section_changed") {
+                continue;
+            }
+            let (sname_from, sname_to, project) =
+                match story_parser.parse(task_gid, asana_story, sgid2pgid) {
+                    Some(section_change) => section_change,
+                    None => continue,
+                };
+            // event may be for a project we are not interested in
+            let pgid: &str = match project {
+                StoryProject::Gid(pgid) => pgid,
+                StoryProject::Name(pname) => {
+                    let pgids: &[&str] = pname2pgids.get(pname).map_or(&[], |pgids| &pgids[..]);
+                    let member_pgids: Vec<&str> = pgids
+                        .iter()
+                        .copied()
+                        .filter(|pgid| task_pgid2sname.contains_key(pgid))
+                        .collect();
+                    if member_pgids.len() != 1 {
+                        story_parser.unresolved(task_gid, pname, member_pgids.len() > 1);
+                        continue;
+                    }
+                    member_pgids[0]
+                }
+            };
+            let section_changed_at: &DateTime<Utc> = &asana_story.created_at;
+            let events = pgid2t_events.entry(pgid).or_default();
+
+            // if a previous event for this task does not exist, it means we are
+            // looking at the first section change event -- in that case
+            // we assume that the task existed in the `sname_from` section at creation.
+            if task_pgids.insert(pgid) {
+                events.push((task_created_at, task_gid, sname_from));
+            }
+            // insert the event for section the task moved to
+            events.push((section_changed_at, task_gid, sname_to));
+        }
+
+        // if a task never changed sections after creation, there is no "section changed" story
+        // so we look for such tasks and synthesize the "create" story
+        for (pgid, task_curr_sname) in task_pgid2sname {
+            if !task_pgids.contains(pgid) {
+                let events = pgid2t_events.entry(pgid).or_default();
+                events.push((task_created_at, task_gid, task_curr_sname));
+            }
+        }
+    }
+
+    // stable, so a task's events at the same instant stay in story order
+    for events in pgid2t_events.values_mut() {
+        events.sort_by_key(|entry| entry.0);
+    }
+
+    pgid2t_events
+}
@@ -0,0 +1,151 @@
+use super::{group_events_by_task, percentile, Percentiles, Stats, TaskEvent};
+use crate::asana::AsanaTask;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// In-progress tasks older than this percentile of their state are flagged.
+pub const AGING_WIP_ALERT_PERCENTILE: u32 = 85;
+
+/// How old the tasks still in progress at the end of the report are, next to how
+/// old delivered tasks were when they left each state.
+#[derive(Debug, Serialize)]
+pub struct AgingWip<'a> {
+    /// The end of the report.
+    pub as_of: DateTime<Utc>,
+    pub percentiles: Vec<u32>,
+    /// The states between the commitment point and delivery, in board order.
+    pub states: Vec<AgingState<'a>>,
+    /// The in-progress tasks, in the order of `states` and oldest first.
+    pub tasks: Vec<AgingTask<'a>>,
+}
+
+/// The age, since commitment, at which delivered tasks last left a state.
+#[derive(Debug, Serialize)]
+pub struct AgingState<'a> {
+    pub state: &'a str,
+    pub age_seconds: Stats,
+    /// The `AGING_WIP_ALERT_PERCENTILE` age.
+    pub alert_age_seconds: Option<u64>,
+}
+
+/// A task in progress at the end of the report.
+#[derive(Debug, Serialize)]
+pub struct AgingTask<'a> {
+    pub task_gid: &'a str,
+    pub name: &'a str,
+    /// The name of the assignee, if the task has one.
+    pub assignee: Option<&'a str>,
+    pub state: &'a str,
+    pub committed_at: DateTime<Utc>,
+    /// From commitment to the end of the report.
+    pub age_seconds: u64,
+    /// Whether the task is older than the `alert_age_seconds` of its state.
+    pub alert: bool,
+}
+
+/// The aging WIP of a project, given events sorted by time and ending at `as_of`.
+/// The in-progress states are those of `committed_states` that are not delivery states.
+pub fn get_aging_wip<'a>(
+    events: &[TaskEvent<'a>],
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    ugid2uname: &HashMap<&str, &'a str>,
+    committed_states: &[&'a str],
+    delivery_states: &[&str],
+    as_of: &DateTime<Utc>,
+    percentiles: &Percentiles,
+) -> AgingWip<'a> {
+    let mut wip_states: Vec<&str> = Vec::new();
+    for &state in committed_states {
+        if !delivery_states.contains(&state) && !wip_states.contains(&state) {
+            wip_states.push(state);
+        }
+    }
+
+    // state => ages at which delivered tasks left it
+    let mut state2exit_ages: HashMap<&str, Vec<u64>> = HashMap::new();
+    let mut tasks: Vec<AgingTask> = Vec::new();
+    let (tgids, tgid2events) = group_events_by_task(events);
+    for tgid in tgids {
+        let task_events = &tgid2events[tgid];
+        let mut committed_at: Option<DateTime<Utc>> = None;
+        // a state visited more than once counts with its last exit
+        let mut state2exit_age: HashMap<&str, u64> = HashMap::new();
+        for (idx, &&(at, _, sname)) in task_events.iter().enumerate() {
+            if committed_at.is_none() && committed_states.contains(&sname) {
+                committed_at = Some(*at);
+            }
+            if let (Some(committed_at), Some(next_event)) = (committed_at, task_events.get(idx + 1))
+            {
+                if wip_states.contains(&sname) {
+                    let age = (*next_event.0 - committed_at).num_seconds().max(0) as u64;
+                    state2exit_age.insert(sname, age);
+                }
+            }
+        }
+        let (_, _, last_sname) = **task_events.last().unwrap();
+        if delivery_states.contains(&last_sname) {
+            for (state, age) in state2exit_age {
+                state2exit_ages.entry(state).or_default().push(age);
+            }
+            continue;
+        }
+        let committed_at = match committed_at {
+            Some(committed_at) if wip_states.contains(&last_sname) => committed_at,
+            _ => continue,
+        };
+        let asana_task = tgid2asana_task[tgid];
+        tasks.push(AgingTask {
+            task_gid: tgid,
+            name: asana_task.name.as_str(),
+            assignee: asana_task.assignee.as_ref().map(|assignee| {
+                ugid2uname
+                    .get(assignee.gid.as_str())
+                    .copied()
+                    .unwrap_or(assignee.gid.as_str())
+            }),
+            state: last_sname,
+            committed_at,
+            age_seconds: (*as_of - committed_at).num_seconds().max(0) as u64,
+            alert: false,
+        });
+    }
+
+    let states: Vec<AgingState> = wip_states
+        .iter()
+        .map(|&state| {
+            let mut ages = state2exit_ages.remove(state).unwrap_or_default();
+            let age_seconds = Stats::new(&mut ages, percentiles);
+            AgingState {
+                state,
+                age_seconds,
+                alert_age_seconds: percentile(
+                    &ages,
+                    AGING_WIP_ALERT_PERCENTILE,
+                    percentiles.interpolation,
+                ),
+            }
+        })
+        .collect();
+
+    for task in tasks.iter_mut() {
+        let aging_state = states.iter().find(|s| s.state == task.state).unwrap();
+        task.alert = aging_state
+            .alert_age_seconds
+            .is_some_and(|alert_age| task.age_seconds > alert_age);
+    }
+    tasks.sort_by_key(|t| {
+        (
+            wip_states.iter().position(|&s| s == t.state),
+            std::cmp::Reverse(t.age_seconds),
+        )
+    });
+
+    AgingWip {
+        as_of: *as_of,
+        percentiles: percentiles.percentiles.to_vec(),
+        states,
+        tasks,
+    }
+}
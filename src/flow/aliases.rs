@@ -0,0 +1,55 @@
+use super::TaskEvent;
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// A section name in the events of a project that is not one of its configured
+/// states, e.g. the old name of a renamed section.
+#[derive(Debug, Serialize)]
+pub struct UnknownState<'a> {
+    pub name: &'a str,
+    pub event_count: u32,
+    pub task_count: u32,
+}
+
+/// `events` with each state renamed to its canonical state in `state_aliases`.
+pub fn apply_state_aliases<'a>(
+    events: &[TaskEvent<'a>],
+    state_aliases: &'a HashMap<String, String>,
+) -> Vec<TaskEvent<'a>> {
+    events
+        .iter()
+        .map(|&(at, tgid, sname)| {
+            let state = state_aliases
+                .get(sname)
+                .map_or(sname, |state| state.as_str());
+            (at, tgid, state)
+        })
+        .collect()
+}
+
+/// The states of `events` that are not in `known_states`, by name.
+pub fn get_unknown_states<'a>(
+    events: &[TaskEvent<'a>],
+    known_states: &[&str],
+) -> Vec<UnknownState<'a>> {
+    // state => (events, tasks)
+    let mut state2events: HashMap<&str, (u32, HashSet<&str>)> = HashMap::new();
+    for &(_, tgid, sname) in events {
+        if !known_states.contains(&sname) {
+            let (event_count, tgids) = state2events.entry(sname).or_default();
+            *event_count += 1;
+            tgids.insert(tgid);
+        }
+    }
+    let mut unknown_states: Vec<UnknownState> = state2events
+        .into_iter()
+        .map(|(name, (event_count, tgids))| UnknownState {
+            name,
+            event_count,
+            task_count: tgids.len() as u32,
+        })
+        .collect();
+    unknown_states.sort_by_key(|s| s.name);
+    unknown_states
+}
@@ -0,0 +1,98 @@
+use super::TaskEvent;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// How many tasks arrived on the board and departed from it in each period.
+#[derive(Debug, Serialize)]
+pub struct FlowRates {
+    /// The number of periods, up to and including each one, in the rolling averages.
+    pub rolling_average_periods: u32,
+    pub periods: Vec<PeriodFlow>,
+}
+
+/// The arrivals and departures of the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodFlow {
+    pub date: NaiveDate,
+    /// Tasks that entered the first of `cfd_states`, or a later one if they skipped
+    /// it, for the first time.
+    pub arrivals: u32,
+    /// Tasks that moved into one of `done_states` from a state that is not done.
+    pub departures: u32,
+    /// Arrivals minus departures; positive when the backlog grows.
+    pub net_flow: i64,
+    /// The net flow since the start of the report.
+    pub cumulative_net_flow: i64,
+    pub arrivals_average: f64,
+    pub departures_average: f64,
+}
+
+/// The arrival and departure rates of a project between `start` and `end`, given
+/// events sorted by time and ending at `end`. `rolling_average_periods` is at least 1.
+pub fn get_flow_rates(
+    events: &[TaskEvent],
+    cfd_states: &[&str],
+    done_states: &[&str],
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    rolling_average_periods: u32,
+) -> FlowRates {
+    let mut arrived_at: Vec<&DateTime<Utc>> = Vec::new();
+    let mut departed_at: Vec<&DateTime<Utc>> = Vec::new();
+    let mut task_state: HashMap<&str, &str> = HashMap::new();
+    let mut arrived_tasks: HashSet<&str> = HashSet::new();
+    for &(at, tgid, sname) in events {
+        let old_state = task_state.insert(tgid, sname);
+        if cfd_states.contains(&sname) && arrived_tasks.insert(tgid) {
+            arrived_at.push(at);
+        }
+        if done_states.contains(&sname) && !old_state.is_some_and(|s| done_states.contains(&s)) {
+            departed_at.push(at);
+        }
+    }
+
+    let mut periods: Vec<PeriodFlow> = Vec::new();
+    let mut cumulative_net_flow = 0;
+    let mut start_of_period = *start;
+    while start_of_period < *end {
+        let start_of_next_period = period.next(&start_of_period);
+        let in_period = |times: &[&DateTime<Utc>]| {
+            times
+                .iter()
+                .filter(|&&&at| at >= start_of_period && at < start_of_next_period)
+                .count() as u32
+        };
+        let arrivals = in_period(&arrived_at);
+        let departures = in_period(&departed_at);
+        let net_flow = i64::from(arrivals) - i64::from(departures);
+        cumulative_net_flow += net_flow;
+        // the rolling averages include this period and up to N - 1 before it
+        let window = std::cmp::min(periods.len(), rolling_average_periods as usize - 1);
+        let recent = &periods[periods.len() - window..];
+        let average = |this: u32, of: fn(&PeriodFlow) -> u32| {
+            (f64::from(this) + recent.iter().map(|p| f64::from(of(p))).sum::<f64>())
+                / (window + 1) as f64
+        };
+        let arrivals_average = average(arrivals, |p| p.arrivals);
+        let departures_average = average(departures, |p| p.departures);
+        periods.push(PeriodFlow {
+            date: start_of_period.date_naive(),
+            arrivals,
+            departures,
+            net_flow,
+            cumulative_net_flow,
+            arrivals_average,
+            departures_average,
+        });
+        start_of_period = start_of_next_period;
+    }
+
+    FlowRates {
+        rolling_average_periods,
+        periods,
+    }
+}
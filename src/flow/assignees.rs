@@ -0,0 +1,204 @@
+use super::{AgingWip, Percentiles, Stats, TaskTimes};
+use crate::asana::{AsanaData, AsanaTask};
+use crate::config::MyConfig;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The name of the group of tasks that have no assignee.
+pub const UNASSIGNED: &str = "Unassigned";
+
+/// Users, or teams of users, and the tasks assigned to them. Asana only gives the
+/// current assignee of a task, so a task counts for whoever has it now.
+#[derive(Debug)]
+pub struct AssigneeGroup<'a> {
+    pub name: &'a str,
+    pub team: bool,
+    pub user_gids: Vec<&'a str>,
+    pub task_gids: HashSet<&'a str>,
+}
+
+/// The flow metrics of the tasks assigned to a user or a team.
+#[derive(Debug, Serialize)]
+pub struct AssigneeFlow<'a> {
+    /// The name of the user, the key of the team in `MyConfig.teams`, or `UNASSIGNED`.
+    pub name: &'a str,
+    pub team: bool,
+    pub user_gids: Vec<&'a str>,
+    /// Tasks delivered during the report.
+    pub delivered_count: u32,
+    pub cycle_time_seconds: Stats,
+    pub periods: Vec<PeriodAssigneeFlow>,
+    /// The tasks in progress at the end of the report, oldest first.
+    pub aging_task_gids: Vec<&'a str>,
+    /// How many of them are older than the alert age of their state.
+    pub alert_count: u32,
+}
+
+/// The throughput and work in progress of an assignee in the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodAssigneeFlow {
+    pub date: NaiveDate,
+    pub delivered_count: u32,
+    /// Tasks committed to but not delivered at the end of the period.
+    pub wip: u32,
+}
+
+/// The teams of `MyConfig.teams`, by name, as user gids. A member is the email of
+/// one of the users in the data, or a user gid.
+pub fn get_teams<'a>(
+    config: &'a MyConfig,
+    asana_data: &'a AsanaData,
+) -> Vec<(&'a str, Vec<&'a str>)> {
+    let email2ugid: HashMap<&str, &str> = asana_data
+        .users
+        .iter()
+        .map(|u| (u.email.as_str(), u.gid.as_str()))
+        .collect();
+    let mut teams: Vec<(&str, Vec<&str>)> = config
+        .teams
+        .iter()
+        .map(|(team, members)| {
+            let user_gids = members
+                .iter()
+                .map(|member| email2ugid.get(member.as_str()).copied().unwrap_or(member))
+                .collect();
+            (team.as_str(), user_gids)
+        })
+        .collect();
+    teams.sort();
+    teams
+}
+
+/// Groups the tasks of `task_times` by assignee, users by name then `UNASSIGNED`,
+/// followed by each of `teams` that has tasks.
+pub fn get_assignee_groups<'a>(
+    task_times: &[TaskTimes<'a>],
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    ugid2uname: &HashMap<&str, &'a str>,
+    teams: &[(&'a str, Vec<&'a str>)],
+) -> Vec<AssigneeGroup<'a>> {
+    let mut ugid2tgids: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut unassigned: HashSet<&str> = HashSet::new();
+    for task_times in task_times {
+        match &tgid2asana_task[task_times.task_gid].assignee {
+            Some(assignee) => ugid2tgids
+                .entry(assignee.gid.as_str())
+                .or_default()
+                .insert(task_times.task_gid),
+            None => unassigned.insert(task_times.task_gid),
+        };
+    }
+
+    let mut groups: Vec<AssigneeGroup> = Vec::new();
+    for (&ugid, task_gids) in &ugid2tgids {
+        groups.push(AssigneeGroup {
+            name: ugid2uname.get(ugid).copied().unwrap_or(ugid),
+            team: false,
+            user_gids: vec![ugid],
+            task_gids: task_gids.clone(),
+        });
+    }
+    groups.sort_by(|a, b| {
+        a.name
+            .cmp(b.name)
+            .then_with(|| a.user_gids.cmp(&b.user_gids))
+    });
+    if !unassigned.is_empty() {
+        groups.push(AssigneeGroup {
+            name: UNASSIGNED,
+            team: false,
+            user_gids: Vec::new(),
+            task_gids: unassigned,
+        });
+    }
+    for (team, user_gids) in teams {
+        let task_gids: HashSet<&str> = user_gids
+            .iter()
+            .filter_map(|ugid| ugid2tgids.get(ugid))
+            .flatten()
+            .copied()
+            .collect();
+        if !task_gids.is_empty() {
+            groups.push(AssigneeGroup {
+                name: team,
+                team: true,
+                user_gids: user_gids.clone(),
+                task_gids,
+            });
+        }
+    }
+    groups
+}
+
+/// The throughput, work in progress, cycle time and aging work of each group
+/// between `start` and `end`. A task is in progress from its commitment until its
+/// delivery, as in `task_times`.
+pub fn get_assignee_flows<'a>(
+    groups: &[AssigneeGroup<'a>],
+    task_times: &[TaskTimes<'a>],
+    aging_wip: &AgingWip<'a>,
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    percentiles: &Percentiles,
+) -> Vec<AssigneeFlow<'a>> {
+    let mut aging_tasks: Vec<(&str, u64, bool)> = aging_wip
+        .tasks
+        .iter()
+        .map(|t| (t.task_gid, t.age_seconds, t.alert))
+        .collect();
+    aging_tasks.sort_by_key(|&(_, age_seconds, _)| std::cmp::Reverse(age_seconds));
+
+    let mut assignees = Vec::new();
+    for group in groups {
+        let tasks: Vec<&TaskTimes> = task_times
+            .iter()
+            .filter(|t| group.task_gids.contains(t.task_gid))
+            .collect();
+        let mut cycle_times: Vec<u64> = tasks.iter().filter_map(|t| t.cycle_time_seconds).collect();
+
+        let mut periods = Vec::new();
+        let mut start_of_period = *start;
+        while start_of_period < *end {
+            let start_of_next_period = period.next(&start_of_period);
+            let as_of = std::cmp::min(start_of_next_period, *end);
+            let delivered_count = tasks
+                .iter()
+                .filter(|t| {
+                    t.delivered_at
+                        .is_some_and(|at| at >= start_of_period && at < start_of_next_period)
+                })
+                .count() as u32;
+            let wip = tasks
+                .iter()
+                .filter(|t| t.committed_at.is_some_and(|at| at < as_of))
+                .filter(|t| t.delivered_at.is_none_or(|at| at >= as_of))
+                .count() as u32;
+            periods.push(PeriodAssigneeFlow {
+                date: start_of_period.date_naive(),
+                delivered_count,
+                wip,
+            });
+            start_of_period = start_of_next_period;
+        }
+
+        let aging: Vec<&(&str, u64, bool)> = aging_tasks
+            .iter()
+            .filter(|(tgid, _, _)| group.task_gids.contains(tgid))
+            .collect();
+        assignees.push(AssigneeFlow {
+            name: group.name,
+            team: group.team,
+            user_gids: group.user_gids.clone(),
+            delivered_count: cycle_times.len() as u32,
+            cycle_time_seconds: Stats::new(&mut cycle_times, percentiles),
+            periods,
+            aging_task_gids: aging.iter().map(|&&(tgid, _, _)| tgid).collect(),
+            alert_count: aging.iter().filter(|&&&(_, _, alert)| alert).count() as u32,
+        });
+    }
+    assignees
+}
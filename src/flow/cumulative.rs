@@ -0,0 +1,139 @@
+use super::TaskEvent;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Cumulative arrivals into each state at the end of the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodCumulativeCounts {
+    pub date: NaiveDate,
+    /// How many tasks had ever reached each of `Cfd.cfd_states` or a later one.
+    /// These never go down from one period to the next.
+    pub cumulative_counts: Vec<u32>,
+}
+
+/// The series of a true cumulative flow diagram: the number of tasks that have
+/// arrived in each state by the end of each period between `start` and `end`,
+/// given events sorted by time and ending at `end`. A task that skips a state, or
+/// moves back, still counts as having arrived in every state up to the furthest
+/// one it reached.
+pub fn get_cumulative_counts(
+    events: &[TaskEvent],
+    cfd_states: &[&str],
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<PeriodCumulativeCounts> {
+    // furthest index into `cfd_states` each task has reached
+    let mut task_furthest: HashMap<&str, usize> = HashMap::new();
+    // how many tasks have reached each state as their furthest
+    let mut furthest_counts: Vec<u32> = vec![0; cfd_states.len()];
+
+    let mut periods = Vec::new();
+    let mut events = events.iter().peekable();
+    let mut start_of_period = *start;
+    while start_of_period < *end {
+        let end_of_period = std::cmp::min(period.next(&start_of_period), *end);
+        while let Some(&(_, tgid, sname)) = events.next_if(|event| *event.0 < end_of_period) {
+            let idx = match cfd_states.iter().position(|&s| s == sname) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            match task_furthest.get_mut(tgid) {
+                Some(furthest) if *furthest >= idx => {}
+                Some(furthest) => {
+                    furthest_counts[*furthest] -= 1;
+                    furthest_counts[idx] += 1;
+                    *furthest = idx;
+                }
+                None => {
+                    furthest_counts[idx] += 1;
+                    task_furthest.insert(tgid, idx);
+                }
+            }
+        }
+        // tasks that reached a state have also arrived in every state before it
+        let mut cumulative_counts = furthest_counts.clone();
+        for idx in (0..cumulative_counts.len().saturating_sub(1)).rev() {
+            cumulative_counts[idx] += cumulative_counts[idx + 1];
+        }
+        periods.push(PeriodCumulativeCounts {
+            date: start_of_period.date_naive(),
+            cumulative_counts,
+        });
+        start_of_period = period.next(&start_of_period);
+    }
+    periods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [&str; 3] = ["Todo", "Doing", "Done"];
+
+    fn counts(events: &[(&str, &str, &str)]) -> Vec<Vec<u32>> {
+        let times: Vec<DateTime<Utc>> = events
+            .iter()
+            .map(|&(date, _, _)| format!("{}T12:00:00Z", date).parse().unwrap())
+            .collect();
+        let events: Vec<TaskEvent> = times
+            .iter()
+            .zip(events)
+            .map(|(at, &(_, tgid, sname))| (at, tgid, sname))
+            .collect();
+        get_cumulative_counts(
+            &events,
+            &STATES,
+            &Period::Daily,
+            &"2024-01-01T00:00:00Z".parse().unwrap(),
+            &"2024-01-04T00:00:00Z".parse().unwrap(),
+        )
+        .into_iter()
+        .map(|p| p.cumulative_counts)
+        .collect()
+    }
+
+    #[test]
+    fn counts_arrivals_per_day() {
+        assert_eq!(
+            counts(&[
+                ("2024-01-01", "a", "Todo"),
+                ("2024-01-01", "b", "Todo"),
+                ("2024-01-02", "a", "Doing"),
+                ("2024-01-03", "a", "Done"),
+            ]),
+            vec![vec![2, 0, 0], vec![2, 1, 0], vec![2, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn skipped_states_count_as_arrived() {
+        assert_eq!(
+            counts(&[("2024-01-01", "a", "Todo"), ("2024-01-02", "a", "Done")]),
+            vec![vec![1, 0, 0], vec![1, 1, 1], vec![1, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn moving_back_keeps_the_furthest_state() {
+        assert_eq!(
+            counts(&[
+                ("2024-01-01", "a", "Doing"),
+                ("2024-01-02", "a", "Todo"),
+                ("2024-01-03", "a", "Doing"),
+            ]),
+            vec![vec![1, 1, 0], vec![1, 1, 0], vec![1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn ignores_states_not_in_the_cfd() {
+        assert_eq!(
+            counts(&[("2024-01-01", "a", "Icebox"), ("2024-01-03", "a", "Todo")]),
+            vec![vec![0, 0, 0], vec![0, 0, 0], vec![1, 0, 0]]
+        );
+    }
+}
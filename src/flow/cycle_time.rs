@@ -0,0 +1,334 @@
+use super::{group_events_by_task, Percentiles, Stats, TaskEvent};
+use crate::asana::AsanaTask;
+use crate::config::MyProjectConfig;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How long tasks took end to end. A task is committed when it first enters
+/// `commitment_point` or any later state of `cfd_states`, and delivered when it last
+/// entered `delivery_states` (if it is still in one of them at the end of the report).
+#[derive(Debug, Serialize)]
+pub struct CycleTimes<'a> {
+    pub commitment_point: &'a str,
+    pub delivery_states: Vec<&'a str>,
+    pub percentiles: Vec<u32>,
+    /// Of all tasks delivered during the report.
+    pub cycle_time_seconds: Stats,
+    pub lead_time_seconds: Stats,
+    /// Every task not delivered before the start of the report, in order of creation.
+    pub tasks: Vec<TaskTimes<'a>>,
+    pub periods: Vec<PeriodTaskTimes>,
+}
+
+/// The commitment and delivery times of one task.
+#[derive(Debug, Serialize)]
+pub struct TaskTimes<'a> {
+    pub task_gid: &'a str,
+    pub name: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub committed_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    /// From commitment to delivery.
+    pub cycle_time_seconds: Option<u64>,
+    /// From creation to delivery.
+    pub lead_time_seconds: Option<u64>,
+}
+
+/// Cycle and lead time of the tasks delivered in the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodTaskTimes {
+    pub date: NaiveDate,
+    pub delivered_count: u32,
+    pub cycle_time_seconds: Stats,
+    pub lead_time_seconds: Stats,
+}
+
+/// The states at and after the commitment point, and the delivery states, of a project.
+/// The commitment point defaults to the first of `cfd_states`; the delivery states
+/// are those at and after `delivery_point` if it is set, else `done_states`.
+pub fn get_commitment_delivery_states(
+    project_config: &MyProjectConfig,
+) -> (&str, Vec<&str>, Vec<&str>) {
+    let cfd_states: Vec<&str> = project_config
+        .cfd_states
+        .iter()
+        .map(|s| s.as_str())
+        .collect();
+    let states_from = |point: &str| -> Vec<&str> {
+        let idx = cfd_states
+            .iter()
+            .position(|&s| s == point)
+            .unwrap_or_else(|| panic!("State {} is not one of cfd_states", point));
+        cfd_states[idx..].to_vec()
+    };
+    let commitment_point: &str = project_config
+        .commitment_point
+        .as_deref()
+        .or_else(|| cfd_states.first().copied())
+        .expect("cfd_states must not be empty");
+    let delivery_states: Vec<&str> = match &project_config.delivery_point {
+        Some(delivery_point) => states_from(delivery_point),
+        None => project_config
+            .done_states
+            .iter()
+            .map(|s| s.as_str())
+            .collect(),
+    };
+    let mut committed_states = states_from(commitment_point);
+    committed_states.extend(delivery_states.iter().copied());
+    (commitment_point, committed_states, delivery_states)
+}
+
+/// The commitment and delivery times of each task with events, given events sorted
+/// by time and ending at the end of the report.
+pub fn get_task_times<'a>(
+    events: &[TaskEvent<'a>],
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    committed_states: &[&str],
+    delivery_states: &[&str],
+    start: &DateTime<Utc>,
+) -> Vec<TaskTimes<'a>> {
+    let (tgids, tgid2events) = group_events_by_task(events);
+
+    let mut task_times: Vec<TaskTimes> = Vec::new();
+    for tgid in tgids {
+        let asana_task = tgid2asana_task[tgid];
+        let mut committed_at: Option<DateTime<Utc>> = None;
+        let mut delivered_at: Option<DateTime<Utc>> = None;
+        for &&(at, _, sname) in &tgid2events[tgid] {
+            if committed_at.is_none() && committed_states.contains(&sname) {
+                committed_at = Some(*at);
+            }
+            if delivery_states.contains(&sname) {
+                // moving between delivery states does not deliver the task again
+                delivered_at = delivered_at.or(Some(*at));
+            } else {
+                // reopened
+                delivered_at = None;
+            }
+        }
+        if delivered_at.is_some_and(|d| d < *start) {
+            continue;
+        }
+        let since = |from: Option<DateTime<Utc>>| match (from, delivered_at) {
+            (Some(from), Some(to)) => Some((to - from).num_seconds().max(0) as u64),
+            _ => None,
+        };
+        task_times.push(TaskTimes {
+            task_gid: tgid,
+            name: asana_task.name.as_str(),
+            created_at: asana_task.created_at,
+            committed_at,
+            delivered_at,
+            cycle_time_seconds: since(committed_at),
+            lead_time_seconds: since(Some(asana_task.created_at)),
+        });
+    }
+    task_times.sort_by_key(|t| t.created_at);
+    task_times
+}
+
+/// Statistics of cycle and lead time of the tasks delivered in each period between
+/// `start` and `end`.
+pub fn get_period_task_times(
+    task_times: &[TaskTimes],
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    percentiles: &Percentiles,
+) -> Vec<PeriodTaskTimes> {
+    let mut periods = Vec::new();
+    let mut start_of_period = *start;
+    while start_of_period < *end {
+        let start_of_next_period = period.next(&start_of_period);
+        let delivered: Vec<&TaskTimes> = task_times
+            .iter()
+            .filter(|t| {
+                t.delivered_at
+                    .is_some_and(|d| d >= start_of_period && d < start_of_next_period)
+            })
+            .collect();
+        let (cycle_time_seconds, lead_time_seconds) = get_stats(delivered.into_iter(), percentiles);
+        periods.push(PeriodTaskTimes {
+            date: start_of_period.date_naive(),
+            delivered_count: lead_time_seconds.count,
+            cycle_time_seconds,
+            lead_time_seconds,
+        });
+        start_of_period = start_of_next_period;
+    }
+    periods
+}
+
+/// Statistics of cycle and lead time of all delivered tasks in `task_times`.
+pub fn get_task_time_stats(task_times: &[TaskTimes], percentiles: &Percentiles) -> (Stats, Stats) {
+    get_stats(task_times.iter(), percentiles)
+}
+
+fn get_stats<'a, 'b: 'a>(
+    task_times: impl Iterator<Item = &'a TaskTimes<'b>>,
+    percentiles: &Percentiles,
+) -> (Stats, Stats) {
+    let mut cycle_time_seconds: Vec<u64> = Vec::new();
+    let mut lead_time_seconds: Vec<u64> = Vec::new();
+    for task_times in task_times {
+        cycle_time_seconds.extend(task_times.cycle_time_seconds);
+        lead_time_seconds.extend(task_times.lead_time_seconds);
+    }
+    (
+        Stats::new(&mut cycle_time_seconds, percentiles),
+        Stats::new(&mut lead_time_seconds, percentiles),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_config(extra: &str) -> MyProjectConfig {
+        serde_json::from_str(&format!(
+            r#"{{"gid": "1", "horizon": "2024-01-01T00:00:00Z",
+                "cfd_states": ["Todo", "Doing", "Review", "Done", "Shipped"],
+                "done_states": ["Done"]{}}}"#,
+            extra
+        ))
+        .unwrap()
+    }
+
+    fn task(created_at: &str) -> AsanaTask {
+        serde_json::from_value(serde_json::json!({
+            "gid": "t1",
+            "name": "Task",
+            "created_at": created_at,
+            "completed": false,
+            "completed_at": null,
+            "assignee": null,
+            "memberships": [],
+        }))
+        .unwrap()
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn commits_at_first_state_and_delivers_in_done_states_by_default() {
+        let config = project_config("");
+        let (commitment_point, committed_states, delivery_states) =
+            get_commitment_delivery_states(&config);
+        assert_eq!(commitment_point, "Todo");
+        assert_eq!(
+            committed_states,
+            vec!["Todo", "Doing", "Review", "Done", "Shipped", "Done"]
+        );
+        assert_eq!(delivery_states, vec!["Done"]);
+    }
+
+    #[test]
+    fn delivery_point_takes_the_states_after_it() {
+        let config = project_config(r#", "commitment_point": "Doing", "delivery_point": "Done""#);
+        let (commitment_point, committed_states, delivery_states) =
+            get_commitment_delivery_states(&config);
+        assert_eq!(commitment_point, "Doing");
+        assert_eq!(
+            committed_states,
+            vec!["Doing", "Review", "Done", "Shipped", "Done", "Shipped"]
+        );
+        assert_eq!(delivery_states, vec!["Done", "Shipped"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "State Later is not one of cfd_states")]
+    fn rejects_unknown_commitment_point() {
+        get_commitment_delivery_states(&project_config(r#", "commitment_point": "Later""#));
+    }
+
+    // (committed at, delivered at, cycle time, lead time)
+    type Times = (
+        Option<DateTime<Utc>>,
+        Option<DateTime<Utc>>,
+        Option<u64>,
+        Option<u64>,
+    );
+
+    // the times of a task created at the time of its first event
+    fn task_times_of(states: &[(&str, &str)]) -> Times {
+        let asana_task = task(states[0].0);
+        let times: Vec<DateTime<Utc>> = states.iter().map(|&(t, _)| at(t)).collect();
+        let events: Vec<TaskEvent> = times
+            .iter()
+            .zip(states)
+            .map(|(t, &(_, sname))| (t, "t1", sname))
+            .collect();
+        let tgid2asana_task: HashMap<&str, &AsanaTask> =
+            vec![("t1", &asana_task)].into_iter().collect();
+        let task_times = get_task_times(
+            &events,
+            &tgid2asana_task,
+            &["Doing", "Done", "Shipped"],
+            &["Done", "Shipped"],
+            &at("2024-01-01T00:00:00Z"),
+        );
+        assert_eq!(task_times.len(), 1);
+        let t = &task_times[0];
+        (
+            t.committed_at,
+            t.delivered_at,
+            t.cycle_time_seconds,
+            t.lead_time_seconds,
+        )
+    }
+
+    #[test]
+    fn moving_between_delivery_states_keeps_the_first_delivery() {
+        let times = task_times_of(&[
+            ("2024-01-01T00:00:00Z", "Todo"),
+            ("2024-01-02T00:00:00Z", "Doing"),
+            ("2024-01-04T00:00:00Z", "Done"),
+            ("2024-01-09T00:00:00Z", "Shipped"),
+        ]);
+        assert_eq!(
+            times,
+            (
+                Some(at("2024-01-02T00:00:00Z")),
+                Some(at("2024-01-04T00:00:00Z")),
+                Some(2 * 86400),
+                Some(3 * 86400)
+            )
+        );
+    }
+
+    #[test]
+    fn reopening_delivers_again_on_reentry() {
+        let times = task_times_of(&[
+            ("2024-01-01T00:00:00Z", "Todo"),
+            ("2024-01-02T00:00:00Z", "Doing"),
+            ("2024-01-04T00:00:00Z", "Done"),
+            ("2024-01-05T00:00:00Z", "Doing"),
+            ("2024-01-08T00:00:00Z", "Done"),
+        ]);
+        assert_eq!(
+            times,
+            (
+                Some(at("2024-01-02T00:00:00Z")),
+                Some(at("2024-01-08T00:00:00Z")),
+                Some(6 * 86400),
+                Some(7 * 86400)
+            )
+        );
+    }
+
+    #[test]
+    fn reopened_task_is_not_delivered() {
+        let times = task_times_of(&[
+            ("2024-01-01T00:00:00Z", "Todo"),
+            ("2024-01-04T00:00:00Z", "Done"),
+            ("2024-01-05T00:00:00Z", "Doing"),
+        ]);
+        assert_eq!(times, (Some(at("2024-01-04T00:00:00Z")), None, None, None));
+    }
+}
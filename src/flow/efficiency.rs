@@ -0,0 +1,170 @@
+use super::{group_events_by_task, TaskEvent, TaskTimes};
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How much of the cycle time of delivered tasks was spent in active states rather
+/// than waiting. Every state not in `waiting_states` is active.
+#[derive(Debug, Serialize)]
+pub struct FlowEfficiency<'a> {
+    pub waiting_states: Vec<&'a str>,
+    /// Active time over cycle time, of all tasks delivered during the report.
+    pub efficiency: Option<f64>,
+    /// Every task delivered during the report, in order of creation.
+    pub tasks: Vec<TaskEfficiency<'a>>,
+    pub periods: Vec<PeriodEfficiency>,
+    /// The waiting states, the one that took the most time first.
+    pub wait_states: Vec<WaitStateTime<'a>>,
+}
+
+/// The active and waiting time of a task between commitment and delivery.
+#[derive(Debug, Serialize)]
+pub struct TaskEfficiency<'a> {
+    pub task_gid: &'a str,
+    pub name: &'a str,
+    pub delivered_at: DateTime<Utc>,
+    pub active_seconds: u64,
+    pub waiting_seconds: u64,
+    /// `None` if the task was delivered as soon as it was committed.
+    pub efficiency: Option<f64>,
+}
+
+/// The flow efficiency of the tasks delivered in the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodEfficiency {
+    pub date: NaiveDate,
+    pub delivered_count: u32,
+    pub active_seconds: u64,
+    pub waiting_seconds: u64,
+    pub efficiency: Option<f64>,
+}
+
+/// The time delivered tasks spent in a waiting state.
+#[derive(Debug, Serialize)]
+pub struct WaitStateTime<'a> {
+    pub state: &'a str,
+    pub seconds: u64,
+    /// How many of the delivered tasks waited in the state.
+    pub task_count: u32,
+    /// The fraction of the cycle time of all delivered tasks spent in the state.
+    pub share: Option<f64>,
+}
+
+/// The flow efficiency of the delivered tasks in `task_times`, given events sorted
+/// by time and ending at the end of the report.
+pub fn get_flow_efficiency<'a>(
+    events: &[TaskEvent<'a>],
+    task_times: &[TaskTimes<'a>],
+    waiting_states: &[&'a str],
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> FlowEfficiency<'a> {
+    let (_, tgid2events) = group_events_by_task(events);
+
+    let mut tasks: Vec<TaskEfficiency> = Vec::new();
+    // state => (seconds, tasks)
+    let mut state2wait: HashMap<&str, (u64, u32)> = HashMap::new();
+    for task_times in task_times {
+        let (committed_at, delivered_at) = match (task_times.committed_at, task_times.delivered_at)
+        {
+            (Some(committed_at), Some(delivered_at)) => (committed_at, delivered_at),
+            _ => continue,
+        };
+        let task_events = &tgid2events[task_times.task_gid];
+        let mut active_seconds = 0;
+        let mut waiting_seconds = 0;
+        let mut task_state2seconds: HashMap<&str, u64> = HashMap::new();
+        for (idx, &&(at, _, sname)) in task_events.iter().enumerate() {
+            // the part of the time in this state that is within the cycle time
+            let left_at = task_events
+                .get(idx + 1)
+                .map_or(delivered_at, |next| *next.0);
+            let from = std::cmp::max(*at, committed_at);
+            let to = std::cmp::min(left_at, delivered_at);
+            if to <= from {
+                continue;
+            }
+            let seconds = (to - from).num_seconds() as u64;
+            if waiting_states.contains(&sname) {
+                waiting_seconds += seconds;
+                *task_state2seconds.entry(sname).or_default() += seconds;
+            } else {
+                active_seconds += seconds;
+            }
+        }
+        for (state, seconds) in task_state2seconds {
+            let wait = state2wait.entry(state).or_default();
+            wait.0 += seconds;
+            wait.1 += 1;
+        }
+        tasks.push(TaskEfficiency {
+            task_gid: task_times.task_gid,
+            name: task_times.name,
+            delivered_at,
+            active_seconds,
+            waiting_seconds,
+            efficiency: efficiency(active_seconds, waiting_seconds),
+        });
+    }
+
+    let mut periods = Vec::new();
+    let mut start_of_period = *start;
+    while start_of_period < *end {
+        let start_of_next_period = period.next(&start_of_period);
+        let delivered: Vec<&TaskEfficiency> = tasks
+            .iter()
+            .filter(|t| t.delivered_at >= start_of_period && t.delivered_at < start_of_next_period)
+            .collect();
+        let active_seconds = delivered.iter().map(|t| t.active_seconds).sum();
+        let waiting_seconds = delivered.iter().map(|t| t.waiting_seconds).sum();
+        periods.push(PeriodEfficiency {
+            date: start_of_period.date_naive(),
+            delivered_count: delivered.len() as u32,
+            active_seconds,
+            waiting_seconds,
+            efficiency: efficiency(active_seconds, waiting_seconds),
+        });
+        start_of_period = start_of_next_period;
+    }
+
+    let active_seconds: u64 = tasks.iter().map(|t| t.active_seconds).sum();
+    let waiting_seconds: u64 = tasks.iter().map(|t| t.waiting_seconds).sum();
+    let total_seconds = active_seconds + waiting_seconds;
+    let mut wait_states: Vec<WaitStateTime> = waiting_states
+        .iter()
+        .map(|&state| {
+            let (seconds, task_count) = state2wait.get(state).copied().unwrap_or_default();
+            WaitStateTime {
+                state,
+                seconds,
+                task_count,
+                share: if total_seconds == 0 {
+                    None
+                } else {
+                    Some(seconds as f64 / total_seconds as f64)
+                },
+            }
+        })
+        .collect();
+    wait_states.sort_by_key(|w| std::cmp::Reverse(w.seconds));
+
+    FlowEfficiency {
+        waiting_states: waiting_states.to_vec(),
+        efficiency: efficiency(active_seconds, waiting_seconds),
+        tasks,
+        periods,
+        wait_states,
+    }
+}
+
+fn efficiency(active_seconds: u64, waiting_seconds: u64) -> Option<f64> {
+    let total_seconds = active_seconds + waiting_seconds;
+    if total_seconds == 0 {
+        None
+    } else {
+        Some(active_seconds as f64 / total_seconds as f64)
+    }
+}
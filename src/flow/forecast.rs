@@ -0,0 +1,334 @@
+use super::PeriodCounts;
+use crate::period::Period;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Confidence levels reported by forecasts.
+pub const FORECAST_CONFIDENCE_LEVELS: [u32; 3] = [50, 85, 95];
+
+/// A possible outcome of a forecast and how often the simulations ended with it.
+#[derive(Debug, Serialize)]
+pub struct Outcome<T> {
+    pub value: T,
+    pub probability: f64,
+    /// The probability of this outcome or a better one: finishing on or before
+    /// the date, or finishing at least the count.
+    pub cumulative_probability: f64,
+}
+
+/// When `items` tasks will be done. Dates are the last day of the period in which
+/// the last of the items is done.
+#[derive(Debug, Serialize)]
+pub struct DateForecast {
+    pub items: u32,
+    pub trials: u32,
+    pub distribution: Vec<Outcome<NaiveDate>>,
+    /// (confidence level, date): the items are done by the date with that confidence.
+    pub confidence: Vec<(u32, NaiveDate)>,
+}
+
+/// How many tasks will be done by the end of the period containing `by`.
+#[derive(Debug, Serialize)]
+pub struct CountForecast {
+    pub by: NaiveDate,
+    pub trials: u32,
+    pub distribution: Vec<Outcome<u32>>,
+    /// (confidence level, count): at least count items are done with that confidence.
+    pub confidence: Vec<(u32, u32)>,
+}
+
+/// The throughput of the periods that ended by `end`; a last, partial period is
+/// left out because its count is not comparable to the others.
+pub fn get_throughput_history(
+    period_counts: &[PeriodCounts],
+    period: &Period,
+    end: &DateTime<Utc>,
+) -> Vec<u32> {
+    period_counts
+        .iter()
+        .filter(|pc| period.next(&pc.date.and_hms_opt(0, 0, 0).unwrap().and_utc()) <= *end)
+        .map(|pc| pc.done_count)
+        .collect()
+}
+
+/// Simulates finishing `items` tasks, drawing the throughput of each period, from
+/// the one containing `from`, at random from `throughput`. Panics if the history
+/// has no period or no task was ever done in it.
+pub fn forecast_completion_dates<R: Rng>(
+    throughput: &[u32],
+    items: u32,
+    period: &Period,
+    from: &DateTime<Utc>,
+    trials: u32,
+    confidence_levels: &[u32],
+    rng: &mut R,
+) -> DateForecast {
+    check_throughput(throughput);
+    // number of periods => number of trials that needed them
+    let mut periods2trials: BTreeMap<u32, u32> = BTreeMap::new();
+    for _ in 0..trials {
+        let mut done = 0;
+        let mut periods = 0;
+        while done < items {
+            done += throughput[rng.gen_range(0..throughput.len())];
+            periods += 1;
+        }
+        *periods2trials.entry(periods).or_default() += 1;
+    }
+
+    let start = period.start_of(from);
+    let mut distribution: Vec<Outcome<NaiveDate>> = Vec::new();
+    let mut cumulative_trials = 0;
+    for (periods, count) in periods2trials {
+        cumulative_trials += count;
+        distribution.push(Outcome {
+            value: last_day_after(period, &start, periods),
+            probability: count as f64 / trials as f64,
+            cumulative_probability: cumulative_trials as f64 / trials as f64,
+        });
+    }
+    let confidence = confidence_levels
+        .iter()
+        .map(|&level| (level, confidence_value(&distribution, level)))
+        .collect();
+    DateForecast {
+        items,
+        trials,
+        distribution,
+        confidence,
+    }
+}
+
+/// Simulates the tasks done in the periods from the one containing `from` to the
+/// one containing `by`, drawing the throughput of each at random from `throughput`.
+/// Panics if the history has no period or no task was ever done in it.
+pub fn forecast_item_counts<R: Rng>(
+    throughput: &[u32],
+    by: NaiveDate,
+    period: &Period,
+    from: &DateTime<Utc>,
+    trials: u32,
+    confidence_levels: &[u32],
+    rng: &mut R,
+) -> CountForecast {
+    check_throughput(throughput);
+    let by_end = (by + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    let mut periods = 0;
+    let mut start_of_period = period.start_of(from);
+    while start_of_period < by_end {
+        periods += 1;
+        start_of_period = period.next(&start_of_period);
+    }
+
+    // number of items => number of trials that finished them
+    let mut items2trials: BTreeMap<u32, u32> = BTreeMap::new();
+    for _ in 0..trials {
+        let items = (0..periods)
+            .map(|_| throughput[rng.gen_range(0..throughput.len())])
+            .sum();
+        *items2trials.entry(items).or_default() += 1;
+    }
+
+    // most items first, so that the cumulative probability is of at least that many
+    let mut distribution: Vec<Outcome<u32>> = Vec::new();
+    let mut cumulative_trials = 0;
+    for (items, count) in items2trials.into_iter().rev() {
+        cumulative_trials += count;
+        distribution.push(Outcome {
+            value: items,
+            probability: count as f64 / trials as f64,
+            cumulative_probability: cumulative_trials as f64 / trials as f64,
+        });
+    }
+    let confidence = confidence_levels
+        .iter()
+        .map(|&level| (level, confidence_value(&distribution, level)))
+        .collect();
+    CountForecast {
+        by,
+        trials,
+        distribution,
+        confidence,
+    }
+}
+
+fn check_throughput(throughput: &[u32]) {
+    if throughput.is_empty() {
+        panic!("Cannot forecast: the window from horizon to end has no complete period");
+    }
+    if throughput.iter().all(|&count| count == 0) {
+        panic!("Cannot forecast: no task was done in any complete period of the history");
+    }
+}
+
+// the last day of the `periods`-th period from the one starting at `start`
+fn last_day_after(period: &Period, start: &DateTime<Utc>, periods: u32) -> NaiveDate {
+    let mut end_of_period = *start;
+    for _ in 0..periods {
+        end_of_period = period.next(&end_of_period);
+    }
+    end_of_period.date_naive() - Duration::days(1)
+}
+
+// the first outcome reached with at least `level` percent of the trials
+fn confidence_value<T: Copy>(distribution: &[Outcome<T>], level: u32) -> T {
+    let level = level as f64 / 100.0;
+    distribution
+        .iter()
+        .find(|outcome| outcome.cumulative_probability >= level - 1e-9)
+        .unwrap_or_else(|| distribution.last().expect("There is at least one trial"))
+        .value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        format!("{}T00:00:00Z", s).parse().unwrap()
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn period_counts(done_counts: &[(&str, u32)]) -> Vec<PeriodCounts> {
+        done_counts
+            .iter()
+            .map(|&(date, done_count)| PeriodCounts {
+                date: day(date),
+                cfd_state_counts: Vec::new(),
+                done_count,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn throughput_history_leaves_out_partial_last_period() {
+        let counts = period_counts(&[("2024-01-01", 3), ("2024-01-08", 5), ("2024-01-15", 1)]);
+        let period = Period::default();
+        assert_eq!(
+            get_throughput_history(&counts, &period, &at("2024-01-18")),
+            vec![3, 5]
+        );
+        assert_eq!(
+            get_throughput_history(&counts, &period, &at("2024-01-22")),
+            vec![3, 5, 1]
+        );
+    }
+
+    #[test]
+    fn completion_dates_with_steady_throughput() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // from a Wednesday, in weeks starting on Monday: 3 weeks for 5 items
+        let forecast = forecast_completion_dates(
+            &[2],
+            5,
+            &Period::default(),
+            &at("2024-01-03"),
+            100,
+            &FORECAST_CONFIDENCE_LEVELS,
+            &mut rng,
+        );
+        assert_eq!(forecast.distribution.len(), 1);
+        assert_eq!(forecast.distribution[0].value, day("2024-01-21"));
+        assert_eq!(forecast.distribution[0].probability, 1.0);
+        assert_eq!(
+            forecast.confidence,
+            vec![
+                (50, day("2024-01-21")),
+                (85, day("2024-01-21")),
+                (95, day("2024-01-21"))
+            ]
+        );
+    }
+
+    #[test]
+    fn completion_dates_are_repeatable_with_a_seed() {
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            forecast_completion_dates(
+                &[0, 3],
+                3,
+                &Period::default(),
+                &at("2024-01-01"),
+                10000,
+                &FORECAST_CONFIDENCE_LEVELS,
+                &mut rng,
+            )
+        };
+        let forecast = run(7);
+        let again = run(7);
+        let values: Vec<NaiveDate> = forecast.distribution.iter().map(|o| o.value).collect();
+        let values_again: Vec<NaiveDate> = again.distribution.iter().map(|o| o.value).collect();
+        assert_eq!(values, values_again);
+        assert_eq!(forecast.confidence, again.confidence);
+
+        // done in the first week half of the time
+        assert_eq!(forecast.distribution[0].value, day("2024-01-07"));
+        assert!((forecast.distribution[0].probability - 0.5).abs() < 0.03);
+        let last = forecast.distribution.last().unwrap();
+        assert!((last.cumulative_probability - 1.0).abs() < 1e-9);
+        assert!(forecast
+            .distribution
+            .windows(2)
+            .all(|w| w[0].value < w[1].value
+                && w[0].cumulative_probability < w[1].cumulative_probability));
+    }
+
+    #[test]
+    fn item_counts_with_steady_throughput() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // the weeks starting on 2024-01-01, 01-08 and 01-15
+        let forecast = forecast_item_counts(
+            &[2],
+            day("2024-01-17"),
+            &Period::default(),
+            &at("2024-01-03"),
+            100,
+            &FORECAST_CONFIDENCE_LEVELS,
+            &mut rng,
+        );
+        assert_eq!(forecast.distribution.len(), 1);
+        assert_eq!(forecast.distribution[0].value, 6);
+        assert_eq!(forecast.confidence, vec![(50, 6), (85, 6), (95, 6)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no task was done")]
+    fn rejects_history_without_throughput() {
+        let mut rng = StdRng::seed_from_u64(1);
+        forecast_completion_dates(
+            &[0, 0],
+            1,
+            &Period::default(),
+            &at("2024-01-01"),
+            10,
+            &FORECAST_CONFIDENCE_LEVELS,
+            &mut rng,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "no complete period")]
+    fn rejects_empty_history() {
+        let mut rng = StdRng::seed_from_u64(1);
+        forecast_item_counts(
+            &[],
+            day("2024-01-17"),
+            &Period::default(),
+            &at("2024-01-01"),
+            10,
+            &FORECAST_CONFIDENCE_LEVELS,
+            &mut rng,
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use super::{apply_state_aliases, Cfd, CycleTimes, TaskEvent};
+use crate::config::{MyConfig, MyPortfolioConfig};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The combined metrics of several projects, with their sections mapped onto a
+/// shared set of stages.
+#[derive(Debug, Serialize)]
+pub struct Portfolio<'a> {
+    /// The key of the portfolio in `MyConfig.portfolios`.
+    pub label: &'a str,
+    /// The labels of its projects, in `MyConfig.projects`.
+    pub projects: Vec<&'a str>,
+    /// Start of the first period.
+    pub start: DateTime<Utc>,
+    /// End of the report; events from then on are ignored.
+    pub end: DateTime<Utc>,
+    /// How many tasks are in the projects, each counted once.
+    pub task_count: u32,
+    /// Its `cfd_states` are the stages of the portfolio.
+    pub cfd: Cfd<'a>,
+    pub cycle_times: CycleTimes<'a>,
+}
+
+/// The events of the projects of a portfolio, sorted by time, with the sections
+/// renamed by the `state_aliases` of their project, then mapped onto its stages.
+/// Sections that are not mapped keep their name. A task that is in several of the
+/// projects only has the events of the first of them. Panics if a project is not in
+/// `config.projects`, or if a stage is unknown.
+pub fn get_portfolio_events<'a>(
+    config: &'a MyConfig,
+    portfolio_config: &'a MyPortfolioConfig,
+    pgid2t_events: &HashMap<&str, Vec<TaskEvent<'a>>>,
+) -> Vec<TaskEvent<'a>> {
+    for stage in &portfolio_config.done_stages {
+        if !portfolio_config.stages.contains(stage) {
+            panic!("Stage {} in done_stages is not one of stages", stage);
+        }
+    }
+
+    let mut seen_tgids: HashSet<&str> = HashSet::new();
+    let mut events: Vec<TaskEvent> = Vec::new();
+    for portfolio_project in &portfolio_config.projects {
+        let project_config = config
+            .projects
+            .get(&portfolio_project.project)
+            .unwrap_or_else(|| {
+                panic!("Unknown project {} in portfolio", portfolio_project.project)
+            });
+        for stage in portfolio_project.stage_map.values() {
+            if !portfolio_config.stages.contains(stage) {
+                panic!("Stage {} in stage_map is not one of stages", stage);
+            }
+        }
+        let project_events = match pgid2t_events.get(project_config.gid.as_str()) {
+            Some(project_events) => {
+                apply_state_aliases(project_events, &project_config.state_aliases)
+            }
+            None => continue,
+        };
+        let project_tgids: HashSet<&str> = project_events
+            .iter()
+            .map(|&(_, tgid, _)| tgid)
+            .filter(|tgid| !seen_tgids.contains(tgid))
+            .collect();
+        events.extend(
+            project_events
+                .iter()
+                .filter(|&&(_, tgid, _)| project_tgids.contains(tgid))
+                .map(|&(at, tgid, sname)| {
+                    let stage = portfolio_project
+                        .stage_map
+                        .get(sname)
+                        .map_or(sname, |stage| stage.as_str());
+                    (at, tgid, stage)
+                }),
+        );
+        seen_tgids.extend(project_tgids);
+    }
+    // stable, so the events of a task at the same time stay in order
+    events.sort_by_key(|event| event.0);
+    events
+}
@@ -0,0 +1,267 @@
+use super::{group_events_by_task, TaskEvent};
+use crate::asana::AsanaTask;
+use crate::period::Period;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Tasks moving backwards on the board. A move is backwards when it goes to an
+/// earlier state of `cfd_states`, or out of `done_states` (a reopened task).
+#[derive(Debug, Serialize)]
+pub struct Rework<'a> {
+    pub periods: Vec<PeriodRework>,
+    /// Every backward transition, the most common first.
+    pub transitions: Vec<BackwardTransition<'a>>,
+    /// Every task that moved backwards, the one with the most bounces first.
+    pub tasks: Vec<TaskRework<'a>>,
+}
+
+/// The moves between states in the period starting at `date`.
+#[derive(Debug, Serialize)]
+pub struct PeriodRework {
+    pub date: NaiveDate,
+    pub moves: u32,
+    pub backward_moves: u32,
+    /// Backward moves over all moves; `None` if nothing moved.
+    pub rework_rate: Option<f64>,
+}
+
+/// How often tasks moved from one state back to another during the report.
+#[derive(Debug, Serialize)]
+pub struct BackwardTransition<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub count: u32,
+}
+
+/// How often a task moved backwards during the report.
+#[derive(Debug, Serialize)]
+pub struct TaskRework<'a> {
+    pub task_gid: &'a str,
+    pub name: &'a str,
+    pub bounces: u32,
+}
+
+/// The backward moves between `start` and `end`, given events sorted by time and
+/// ending at `end`.
+pub fn get_rework<'a>(
+    events: &[TaskEvent<'a>],
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    cfd_states: &[&str],
+    done_states: &[&str],
+    period: &Period,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Rework<'a> {
+    let is_backward = |from: &str, to: &str| {
+        let from_idx = cfd_states.iter().position(|&s| s == from);
+        let to_idx = cfd_states.iter().position(|&s| s == to);
+        let moved_back = match (from_idx, to_idx) {
+            (Some(from_idx), Some(to_idx)) => to_idx < from_idx,
+            _ => false,
+        };
+        moved_back || done_states.contains(&from) && !done_states.contains(&to)
+    };
+
+    // (time, backward) of every move in the report
+    let mut moves: Vec<(&DateTime<Utc>, bool)> = Vec::new();
+    let mut transition2count: HashMap<(&str, &str), u32> = HashMap::new();
+    let mut tasks: Vec<TaskRework> = Vec::new();
+    let (tgids, tgid2events) = group_events_by_task(events);
+    for tgid in tgids {
+        let mut bounces = 0;
+        for pair in tgid2events[tgid].windows(2) {
+            let (_, _, from) = *pair[0];
+            let (at, _, to) = *pair[1];
+            if at < start || from == to {
+                continue;
+            }
+            let backward = is_backward(from, to);
+            if backward {
+                bounces += 1;
+                *transition2count.entry((from, to)).or_default() += 1;
+            }
+            moves.push((at, backward));
+        }
+        if bounces > 0 {
+            tasks.push(TaskRework {
+                task_gid: tgid,
+                name: tgid2asana_task[tgid].name.as_str(),
+                bounces,
+            });
+        }
+    }
+    // stable, so tasks with as many bounces stay in order of their first event
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.bounces));
+
+    let mut transitions: Vec<BackwardTransition> = transition2count
+        .into_iter()
+        .map(|((from, to), count)| BackwardTransition { from, to, count })
+        .collect();
+    transitions.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.from.cmp(b.from))
+            .then_with(|| a.to.cmp(b.to))
+    });
+
+    let mut periods = Vec::new();
+    let mut start_of_period = *start;
+    while start_of_period < *end {
+        let start_of_next_period = period.next(&start_of_period);
+        let in_period: Vec<bool> = moves
+            .iter()
+            .filter(|&&(at, _)| *at >= start_of_period && *at < start_of_next_period)
+            .map(|&(_, backward)| backward)
+            .collect();
+        let backward_moves = in_period.iter().filter(|&&backward| backward).count() as u32;
+        periods.push(PeriodRework {
+            date: start_of_period.date_naive(),
+            moves: in_period.len() as u32,
+            backward_moves,
+            rework_rate: if in_period.is_empty() {
+                None
+            } else {
+                Some(f64::from(backward_moves) / in_period.len() as f64)
+            },
+        });
+        start_of_period = start_of_next_period;
+    }
+
+    Rework {
+        periods,
+        transitions,
+        tasks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(gid: &str) -> AsanaTask {
+        serde_json::from_value(serde_json::json!({
+            "gid": gid,
+            "name": format!("Task {}", gid),
+            "created_at": "2024-01-01T00:00:00Z",
+            "completed": false,
+            "completed_at": null,
+            "assignee": null,
+            "memberships": [],
+        }))
+        .unwrap()
+    }
+
+    // (moves, backward moves) of each day, backward transitions and bounces per task
+    type Summary = (
+        Vec<(u32, u32)>,
+        Vec<(String, String, u32)>,
+        Vec<(String, u32)>,
+    );
+
+    fn rework(events: &[(&str, &str, &str)]) -> Summary {
+        let times: Vec<DateTime<Utc>> = events
+            .iter()
+            .map(|&(date, _, _)| format!("{}T12:00:00Z", date).parse().unwrap())
+            .collect();
+        let events: Vec<TaskEvent> = times
+            .iter()
+            .zip(events)
+            .map(|(at, &(_, tgid, sname))| (at, tgid, sname))
+            .collect();
+        let tasks: Vec<AsanaTask> = vec![task("a"), task("b")];
+        let tgid2asana_task: HashMap<&str, &AsanaTask> =
+            tasks.iter().map(|t| (t.gid.as_str(), t)).collect();
+        let rework = get_rework(
+            &events,
+            &tgid2asana_task,
+            &["Todo", "Doing", "Done"],
+            &["Done", "Archived"],
+            &Period::Daily,
+            &"2024-01-02T00:00:00Z".parse().unwrap(),
+            &"2024-01-05T00:00:00Z".parse().unwrap(),
+        );
+        (
+            rework
+                .periods
+                .iter()
+                .map(|p| (p.moves, p.backward_moves))
+                .collect(),
+            rework
+                .transitions
+                .iter()
+                .map(|t| (t.from.to_owned(), t.to.to_owned(), t.count))
+                .collect(),
+            rework
+                .tasks
+                .iter()
+                .map(|t| (t.task_gid.to_owned(), t.bounces))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn counts_moves_to_earlier_states() {
+        let (periods, transitions, tasks) = rework(&[
+            ("2024-01-02", "a", "Todo"),
+            ("2024-01-02", "a", "Doing"),
+            ("2024-01-03", "a", "Todo"),
+            ("2024-01-04", "a", "Doing"),
+        ]);
+        assert_eq!(periods, vec![(1, 0), (1, 1), (1, 0)]);
+        assert_eq!(
+            transitions,
+            vec![("Doing".to_owned(), "Todo".to_owned(), 1)]
+        );
+        assert_eq!(tasks, vec![("a".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn reopening_a_done_task_is_backward() {
+        // "Archived" is a done state that is not in cfd_states
+        let (periods, transitions, _) = rework(&[
+            ("2024-01-02", "a", "Done"),
+            ("2024-01-03", "a", "Archived"),
+            ("2024-01-04", "a", "Doing"),
+        ]);
+        assert_eq!(periods, vec![(0, 0), (1, 0), (1, 1)]);
+        assert_eq!(
+            transitions,
+            vec![("Archived".to_owned(), "Doing".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn ignores_moves_before_the_report() {
+        let (periods, transitions, tasks) = rework(&[
+            ("2024-01-01", "a", "Doing"),
+            ("2024-01-01", "a", "Todo"),
+            ("2024-01-03", "a", "Doing"),
+        ]);
+        assert_eq!(periods, vec![(0, 0), (1, 0), (0, 0)]);
+        assert!(transitions.is_empty());
+        assert!(tasks.is_empty());
+    }
+
+    #[test]
+    fn orders_tasks_by_bounces() {
+        let (_, transitions, tasks) = rework(&[
+            ("2024-01-02", "a", "Doing"),
+            ("2024-01-02", "b", "Done"),
+            ("2024-01-03", "a", "Todo"),
+            ("2024-01-03", "b", "Doing"),
+            ("2024-01-04", "b", "Done"),
+            ("2024-01-04", "b", "Todo"),
+        ]);
+        assert_eq!(tasks, vec![("b".to_owned(), 2), ("a".to_owned(), 1)]);
+        assert_eq!(
+            transitions,
+            vec![
+                ("Doing".to_owned(), "Todo".to_owned(), 1),
+                ("Done".to_owned(), "Doing".to_owned(), 1),
+                ("Done".to_owned(), "Todo".to_owned(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use super::{Cfd, CycleTimes, TaskEvent};
+use crate::asana::AsanaTask;
+use crate::config::MySegmentConfig;
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The metrics of the tasks of a project that belong to a segment of its config,
+/// e.g. a class of service, to compare with the other segments.
+#[derive(Debug, Serialize)]
+pub struct Segment<'a> {
+    /// The key of the segment in `MyProjectConfig.segments`.
+    pub name: &'a str,
+    /// How many of the project's tasks are in the segment.
+    pub task_count: u32,
+    pub cfd: Cfd<'a>,
+    pub cycle_times: CycleTimes<'a>,
+}
+
+/// The tasks with events in `events` that belong to a segment. Panics if the
+/// segment has an invalid `name_regex`.
+pub fn get_segment_tgids<'a>(
+    events: &[TaskEvent<'a>],
+    tgid2asana_task: &HashMap<&str, &AsanaTask>,
+    segment_config: &MySegmentConfig,
+) -> HashSet<&'a str> {
+    let name_regex = match segment_config {
+        MySegmentConfig::NameRegex { name_regex } => Some(
+            Regex::new(name_regex)
+                .unwrap_or_else(|err| panic!("Invalid name_regex {}: {}", name_regex, err)),
+        ),
+        _ => None,
+    };
+    let in_segment = |asana_task: &AsanaTask| match segment_config {
+        MySegmentConfig::Tag { tag } => asana_task.tags.iter().any(|t| &t.name == tag),
+        MySegmentConfig::CustomField {
+            custom_field,
+            value,
+        } => asana_task
+            .custom_fields
+            .iter()
+            .any(|f| &f.name == custom_field && f.display_value.as_ref() == Some(value)),
+        MySegmentConfig::NameRegex { .. } => {
+            name_regex.as_ref().unwrap().is_match(&asana_task.name)
+        }
+    };
+    events
+        .iter()
+        .map(|&(_, tgid, _)| tgid)
+        .filter(|tgid| in_segment(tgid2asana_task[tgid]))
+        .collect()
+}
@@ -0,0 +1,181 @@
+use crate::config::Interpolation;
+
+use serde::Serialize;
+
+/// Which percentiles `Stats` have, and how they are computed.
+#[derive(Debug, Clone, Copy)]
+pub struct Percentiles<'a> {
+    pub percentiles: &'a [u32],
+    pub interpolation: Interpolation,
+}
+
+/// Summary statistics of a set of values; all but `count` are `None` if it is empty.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub count: u32,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    /// Rounded to the nearest whole value.
+    pub mean: Option<u64>,
+    /// One value per requested percentile.
+    pub percentiles: Vec<Option<u64>>,
+}
+
+impl Stats {
+    /// The statistics of `values`, which are sorted in place.
+    pub fn new(values: &mut [u64], percentiles: &Percentiles) -> Stats {
+        values.sort_unstable();
+        let count = values.len();
+        Stats {
+            count: count as u32,
+            min: values.first().copied(),
+            max: values.last().copied(),
+            mean: if count == 0 {
+                None
+            } else {
+                let sum: u128 = values.iter().map(|&v| u128::from(v)).sum();
+                Some(((sum + count as u128 / 2) / count as u128) as u64)
+            },
+            percentiles: percentiles
+                .percentiles
+                .iter()
+                .map(|&pct| percentile(values, pct, percentiles.interpolation))
+                .collect(),
+        }
+    }
+}
+
+/// The `pct`th percentile of a sorted slice, or `None` if it is empty.
+pub fn percentile(vec: &[u64], pct: u32, interpolation: Interpolation) -> Option<u64> {
+    if vec.is_empty() {
+        return None;
+    }
+    let rank = (vec.len() - 1) as f64 * f64::from(pct.min(100)) / 100.0;
+    let lower = vec[rank.floor() as usize];
+    let higher = vec[rank.ceil() as usize];
+    let value = match interpolation {
+        Interpolation::Lower => lower,
+        Interpolation::Higher => higher,
+        Interpolation::Nearest => vec[rank.round() as usize],
+        Interpolation::Midpoint => lower + (higher - lower) / 2,
+        Interpolation::Linear => lower + ((higher - lower) as f64 * rank.fract()).round() as u64,
+    };
+    Some(value)
+}
+
+/// Panics if a percentile is over 100.
+pub fn validate_percentiles(percentiles: &[u32]) {
+    if let Some(pct) = percentiles.iter().find(|&&pct| pct > 100) {
+        panic!("Percentile {} is over 100", pct);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Interpolation; 5] = [
+        Interpolation::Lower,
+        Interpolation::Higher,
+        Interpolation::Nearest,
+        Interpolation::Midpoint,
+        Interpolation::Linear,
+    ];
+
+    #[test]
+    fn percentile_of_empty_is_none() {
+        for &interpolation in &ALL {
+            assert_eq!(percentile(&[], 50, interpolation), None);
+        }
+    }
+
+    #[test]
+    fn percentile_of_single_value_is_that_value() {
+        for &interpolation in &ALL {
+            for &pct in &[0, 50, 100] {
+                assert_eq!(percentile(&[7], pct, interpolation), Some(7));
+            }
+        }
+    }
+
+    #[test]
+    fn p0_and_p100_are_min_and_max() {
+        let values = [1, 3, 10, 20];
+        for &interpolation in &ALL {
+            assert_eq!(percentile(&values, 0, interpolation), Some(1));
+            assert_eq!(percentile(&values, 100, interpolation), Some(20));
+        }
+    }
+
+    #[test]
+    fn percentile_over_100_is_the_max() {
+        assert_eq!(percentile(&[1, 2, 3], 150, Interpolation::Linear), Some(3));
+    }
+
+    #[test]
+    fn percentile_between_values() {
+        // rank = 3 * 0.5 = 1.5, between 10 and 20
+        let values = [0, 10, 20, 30];
+        let at = |interpolation| percentile(&values, 50, interpolation);
+        assert_eq!(at(Interpolation::Lower), Some(10));
+        assert_eq!(at(Interpolation::Higher), Some(20));
+        assert_eq!(at(Interpolation::Nearest), Some(20));
+        assert_eq!(at(Interpolation::Midpoint), Some(15));
+        assert_eq!(at(Interpolation::Linear), Some(15));
+
+        // rank = 3 * 0.9 = 2.7, between 20 and 30
+        let at = |interpolation| percentile(&values, 90, interpolation);
+        assert_eq!(at(Interpolation::Lower), Some(20));
+        assert_eq!(at(Interpolation::Higher), Some(30));
+        assert_eq!(at(Interpolation::Nearest), Some(30));
+        assert_eq!(at(Interpolation::Midpoint), Some(25));
+        assert_eq!(at(Interpolation::Linear), Some(27));
+    }
+
+    #[test]
+    fn percentile_on_a_value_is_that_value() {
+        // rank = 4 * 0.25 = 1
+        let values = [0, 10, 20, 30, 40];
+        for &interpolation in &ALL {
+            assert_eq!(percentile(&values, 25, interpolation), Some(10));
+        }
+    }
+
+    #[test]
+    fn stats_of_values() {
+        let mut values = [30, 10, 20, 40];
+        let percentiles = Percentiles {
+            percentiles: &[0, 50, 100],
+            interpolation: Interpolation::Lower,
+        };
+        assert_eq!(
+            Stats::new(&mut values, &percentiles),
+            Stats {
+                count: 4,
+                min: Some(10),
+                max: Some(40),
+                mean: Some(25),
+                percentiles: vec![Some(10), Some(20), Some(40)],
+            }
+        );
+        assert_eq!(values, [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn stats_of_no_values() {
+        let percentiles = Percentiles {
+            percentiles: &[50],
+            interpolation: Interpolation::Lower,
+        };
+        assert_eq!(
+            Stats::new(&mut [], &percentiles),
+            Stats {
+                count: 0,
+                min: None,
+                max: None,
+                mean: None,
+                percentiles: vec![None],
+            }
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use crate::asana::AsanaStory;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The known templates of the text of "section_changed" stories, by name. Each has
+/// the named groups `from`, `to` and `project`.
+///
+/// - `"en"`: the English text in data fetched from Asana, e.g. `moved this Task from
+///   "To do" to "Doing" in Board`, in any letter case. Section names may contain quotes.
+///
+/// Only this one is built in: it is the only text seen in fetched data, and the
+/// wording Asana uses for other locales is not documented. Accounts in other
+/// languages, or texts Asana changes, need `MyConfig.section_changed_patterns`.
+pub const SECTION_CHANGED_TEMPLATES: [(&str, &str); 1] = [(
+    "en",
+    r#"(?i)^moved this task from "(?P<from>.*)" to "(?P<to>.*)" in (?P<project>.+)$"#,
+)];
+
+const TEMPLATE_GROUPS: [&str; 3] = ["from", "to", "project"];
+
+/// How the "section_changed" stories were read.
+#[derive(Debug, Serialize)]
+pub struct StoryParsing<'a> {
+    /// Stories read from their `old_section` and `new_section`.
+    pub structured_count: u32,
+    /// How many stories the text of each template matched, in the order they are tried.
+    pub templates: Vec<TemplateCount>,
+    /// Stories that could not be read, in the order of the data.
+    pub unparsed: Vec<UnparsedStory<'a>>,
+    /// Projects named in stories that are not one project of the data, by name.
+    pub unresolved_projects: Vec<UnresolvedProject<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateCount {
+    /// The name of one of `SECTION_CHANGED_TEMPLATES`, or `config N` for the Nth
+    /// of `MyConfig.section_changed_patterns`.
+    pub template: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnparsedStory<'a> {
+    pub task_gid: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub text: &'a str,
+}
+
+/// A project named in the text of stories that is not one project in the data the
+/// task is in, e.g. another project the task is also in, or one renamed since.
+#[derive(Debug, Serialize)]
+pub struct UnresolvedProject<'a> {
+    pub name: &'a str,
+    /// Whether the task is in several projects in the data with the name.
+    pub ambiguous: bool,
+    pub story_count: u32,
+    pub task_count: u32,
+}
+
+/// The project of a "section_changed" story: its gid, from the structured fields of
+/// the story, or its name at the time, from the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoryProject<'a> {
+    Gid(&'a str),
+    Name(&'a str),
+}
+
+/// Reads "section_changed" stories into (from section, to section, project),
+/// keeping count of how each was read.
+pub struct StoryParser<'a> {
+    templates: Vec<(String, Regex)>,
+    parsing: StoryParsing<'a>,
+    // (project name, ambiguous) => (stories, tasks)
+    unresolved: HashMap<(&'a str, bool), (u32, HashSet<&'a str>)>,
+}
+
+impl<'a> StoryParser<'a> {
+    /// A parser that tries `patterns`, in order, before the built-in templates.
+    /// Panics if a pattern is not a valid regex or lacks one of the named groups
+    /// `from`, `to` and `project`.
+    pub fn new(patterns: &[String]) -> Self {
+        let mut templates: Vec<(String, Regex)> = Vec::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let re = Regex::new(pattern).unwrap_or_else(|err| {
+                panic!("Invalid section_changed_patterns {}: {}", pattern, err)
+            });
+            for group in TEMPLATE_GROUPS.iter() {
+                if !re.capture_names().any(|name| name == Some(group)) {
+                    panic!(
+                        "Pattern {} in section_changed_patterns has no group named {}",
+                        pattern, group
+                    );
+                }
+            }
+            templates.push((format!("config {}", idx + 1), re));
+        }
+        for (version, template) in SECTION_CHANGED_TEMPLATES.iter() {
+            templates.push((version.to_string(), Regex::new(template).unwrap()));
+        }
+        let parsing = StoryParsing {
+            structured_count: 0,
+            templates: templates
+                .iter()
+                .map(|(template, _)| TemplateCount {
+                    template: template.clone(),
+                    count: 0,
+                })
+                .collect(),
+            unparsed: Vec::new(),
+            unresolved_projects: Vec::new(),
+        };
+        StoryParser {
+            templates,
+            parsing,
+            unresolved: HashMap::new(),
+        }
+    }
+
+    /// Reads a "section_changed" story of a task. The structured fields of the story
+    /// are used when both sections are set and the new one is in `sgid2pgid`; the
+    /// section names are then the current ones. Otherwise the text is matched against
+    /// each template in turn. Returns `None`, and records the story, if neither works.
+    pub fn parse(
+        &mut self,
+        task_gid: &'a str,
+        story: &'a AsanaStory,
+        sgid2pgid: &HashMap<&str, &'a str>,
+    ) -> Option<(&'a str, &'a str, StoryProject<'a>)> {
+        if let (Some(old_section), Some(new_section)) = (&story.old_section, &story.new_section) {
+            if let Some(&pgid) = sgid2pgid.get(new_section.gid.as_str()) {
+                self.parsing.structured_count += 1;
+                return Some((
+                    old_section.name.as_str(),
+                    new_section.name.as_str(),
+                    StoryProject::Gid(pgid),
+                ));
+            }
+        }
+        let text = story.text.as_str();
+        for (idx, (_, re)) in self.templates.iter().enumerate() {
+            if let Some(caps) = re.captures(text) {
+                self.parsing.templates[idx].count += 1;
+                return Some((
+                    caps.name("from").unwrap().as_str(),
+                    caps.name("to").unwrap().as_str(),
+                    StoryProject::Name(caps.name("project").unwrap().as_str()),
+                ));
+            }
+        }
+        log::warn!("Could not parse story of task {}: {}", task_gid, text);
+        self.parsing.unparsed.push(UnparsedStory {
+            task_gid,
+            created_at: story.created_at,
+            text,
+        });
+        None
+    }
+
+    /// Records a story of a task whose project, named `pname`, is not one project of
+    /// the data.
+    pub fn unresolved(&mut self, task_gid: &'a str, pname: &'a str, ambiguous: bool) {
+        let (story_count, tgids) = self.unresolved.entry((pname, ambiguous)).or_default();
+        *story_count += 1;
+        tgids.insert(task_gid);
+    }
+
+    pub fn finish(mut self) -> StoryParsing<'a> {
+        let mut unresolved_projects: Vec<UnresolvedProject> = self
+            .unresolved
+            .into_iter()
+            .map(
+                |((name, ambiguous), (story_count, tgids))| UnresolvedProject {
+                    name,
+                    ambiguous,
+                    story_count,
+                    task_count: tgids.len() as u32,
+                },
+            )
+            .collect();
+        unresolved_projects.sort_by_key(|p| (p.name, p.ambiguous));
+        self.parsing.unresolved_projects = unresolved_projects;
+        self.parsing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asana::AsanaSection;
+
+    fn story(text: &str) -> AsanaStory {
+        AsanaStory {
+            created_at: "2024-01-02T03:04:05Z".parse().unwrap(),
+            resource_subtype: "section_changed".to_owned(),
+            text: text.to_owned(),
+            old_section: None,
+            new_section: None,
+        }
+    }
+
+    fn section(gid: &str, name: &str) -> Option<AsanaSection> {
+        Some(AsanaSection {
+            gid: gid.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    fn parse<'a>(
+        parser: &mut StoryParser<'a>,
+        story: &'a AsanaStory,
+    ) -> Option<(&'a str, &'a str, StoryProject<'a>)> {
+        parser.parse("1", story, &HashMap::new())
+    }
+
+    #[test]
+    fn parses_english_template() {
+        let mut parser = StoryParser::new(&[]);
+        let story = story(r#"moved this Task from "To do" to "Doing" in Board"#);
+        assert_eq!(
+            parse(&mut parser, &story),
+            Some(("To do", "Doing", StoryProject::Name("Board")))
+        );
+        let parsing = parser.finish();
+        assert_eq!(parsing.templates[0].template, "en");
+        assert_eq!(parsing.templates[0].count, 1);
+    }
+
+    #[test]
+    fn parses_lowercase_task() {
+        let mut parser = StoryParser::new(&[]);
+        let story = story(r#"moved this task from "To do" to "Doing" in Board"#);
+        assert_eq!(
+            parse(&mut parser, &story),
+            Some(("To do", "Doing", StoryProject::Name("Board")))
+        );
+    }
+
+    #[test]
+    fn parses_quotes_in_section_names() {
+        let mut parser = StoryParser::new(&[]);
+        let story = story(r#"moved this Task from "The "old" one" to "Done" in Board"#);
+        assert_eq!(
+            parse(&mut parser, &story),
+            Some(("The \"old\" one", "Done", StoryProject::Name("Board")))
+        );
+    }
+
+    #[test]
+    fn tries_config_patterns_first() {
+        let patterns = vec![
+            r"^a déplacé cette tâche de « (?P<from>.+) » vers « (?P<to>.+) » dans (?P<project>.+)$"
+                .to_owned(),
+        ];
+        let mut parser = StoryParser::new(&patterns);
+        let french = story("a déplacé cette tâche de « À faire » vers « En cours » dans Board");
+        let english = story(r#"moved this Task from "To do" to "Doing" in Board"#);
+        assert_eq!(
+            parse(&mut parser, &french),
+            Some(("À faire", "En cours", StoryProject::Name("Board")))
+        );
+        assert!(parse(&mut parser, &english).is_some());
+        let parsing = parser.finish();
+        let counts: Vec<(&str, u32)> = parsing
+            .templates
+            .iter()
+            .map(|t| (t.template.as_str(), t.count))
+            .collect();
+        assert_eq!(counts, vec![("config 1", 1), ("en", 1)]);
+    }
+
+    #[test]
+    fn leaves_other_languages_to_config_patterns() {
+        let mut parser = StoryParser::new(&[]);
+        let story = story("a déplacé cette tâche de « À faire » vers « En cours » dans Board");
+        assert_eq!(parse(&mut parser, &story), None);
+        assert_eq!(parser.finish().unparsed.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no group named project")]
+    fn rejects_pattern_without_group() {
+        StoryParser::new(&[r"^(?P<from>.+) -> (?P<to>.+)$".to_owned()]);
+    }
+
+    #[test]
+    fn records_unparsed_stories() {
+        let mut parser = StoryParser::new(&[]);
+        let story = story("moved this Task somewhere else");
+        assert_eq!(parse(&mut parser, &story), None);
+        let parsing = parser.finish();
+        assert_eq!(parsing.templates[0].count, 0);
+        assert_eq!(parsing.unparsed.len(), 1);
+        assert_eq!(parsing.unparsed[0].task_gid, "1");
+        assert_eq!(parsing.unparsed[0].text, "moved this Task somewhere else");
+    }
+
+    #[test]
+    fn prefers_story_fields() {
+        let mut parser = StoryParser::new(&[]);
+        let mut story = story(r#"moved this Task from "Old name" to "Doing" in Old board"#);
+        story.old_section = section("s1", "To do");
+        story.new_section = section("s2", "Doing");
+        let sgid2pgid: HashMap<&str, &str> = vec![("s1", "p1"), ("s2", "p1")].into_iter().collect();
+        assert_eq!(
+            parser.parse("1", &story, &sgid2pgid),
+            Some(("To do", "Doing", StoryProject::Gid("p1")))
+        );
+        assert_eq!(parser.finish().structured_count, 1);
+    }
+
+    #[test]
+    fn falls_back_to_text_for_sections_not_in_data() {
+        let mut parser = StoryParser::new(&[]);
+        let mut story = story(r#"moved this Task from "To do" to "Doing" in Other"#);
+        story.old_section = section("s8", "To do");
+        story.new_section = section("s9", "Doing");
+        assert_eq!(
+            parse(&mut parser, &story),
+            Some(("To do", "Doing", StoryProject::Name("Other")))
+        );
+        assert_eq!(parser.finish().structured_count, 0);
+    }
+}
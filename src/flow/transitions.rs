@@ -0,0 +1,303 @@
+use super::{group_events_by_task, percentile, TaskEvent};
+use crate::asana::AsanaTask;
+use crate::config::{Interpolation, MyProjectConfig};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The moves of tasks between states during the report: a state-transition matrix.
+#[derive(Debug, Serialize)]
+pub struct Transitions<'a> {
+    /// The rows and columns of the matrix: `cfd_states`, then any other state the
+    /// tasks moved from or to, in order of first appearance.
+    pub states: Vec<&'a str>,
+    /// Every pair of states tasks moved between, in the order of `states`.
+    pub transitions: Vec<Transition<'a>>,
+    /// The states of `cfd_states` no task should move past without entering.
+    pub required_states: Vec<&'a str>,
+    /// Every move past a required state the task had never been in, in time order.
+    pub skips: Vec<StageSkip<'a>>,
+}
+
+/// How often tasks moved from one state straight to another.
+#[derive(Debug, Serialize)]
+pub struct Transition<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub count: u32,
+    /// The median time tasks spent in `from` before moving to `to`.
+    pub median_seconds: Option<u64>,
+}
+
+/// A task moving past required states it had never been in.
+#[derive(Debug, Serialize)]
+pub struct StageSkip<'a> {
+    pub task_gid: &'a str,
+    pub name: &'a str,
+    pub at: DateTime<Utc>,
+    pub from: &'a str,
+    pub to: &'a str,
+    /// The required states before `to` in `cfd_states` that the task skipped.
+    pub skipped_states: Vec<&'a str>,
+}
+
+/// The required states of a project, in the order of `cfd_states`. Panics if one
+/// is not one of `cfd_states`.
+pub fn get_required_states(project_config: &MyProjectConfig) -> Vec<&str> {
+    for state in &project_config.required_states {
+        if !project_config.cfd_states.contains(state) {
+            panic!(
+                "State {} in required_states is not one of cfd_states",
+                state
+            );
+        }
+    }
+    project_config
+        .cfd_states
+        .iter()
+        .filter(|state| project_config.required_states.contains(state))
+        .map(|state| state.as_str())
+        .collect()
+}
+
+/// The moves between states from `start` on, given events sorted by time and
+/// ending at the end of the report. A task skips a required state when it moves to
+/// a later state of `cfd_states` without ever having been in it; each skipped state
+/// is reported once per task.
+pub fn get_transitions<'a>(
+    events: &[TaskEvent<'a>],
+    tgid2asana_task: &HashMap<&str, &'a AsanaTask>,
+    cfd_states: &[&'a str],
+    required_states: &[&'a str],
+    interpolation: Interpolation,
+    start: &DateTime<Utc>,
+) -> Transitions<'a> {
+    let mut states: Vec<&str> = cfd_states.to_vec();
+    // (from, to) => times spent in `from` before moving to `to`
+    let mut transition2seconds: HashMap<(&str, &str), Vec<u64>> = HashMap::new();
+    let mut skips: Vec<StageSkip> = Vec::new();
+    let (tgids, tgid2events) = group_events_by_task(events);
+    for tgid in tgids {
+        let task_events = &tgid2events[tgid];
+        let mut visited: HashSet<&str> = HashSet::new();
+        visited.insert(task_events[0].2);
+        for pair in task_events.windows(2) {
+            let (entered_at, _, from) = *pair[0];
+            let (at, _, to) = *pair[1];
+            if at < start || from == to {
+                visited.insert(to);
+                continue;
+            }
+            for state in [from, to] {
+                if !states.contains(&state) {
+                    states.push(state);
+                }
+            }
+            transition2seconds
+                .entry((from, to))
+                .or_default()
+                .push((*at - *entered_at).num_seconds() as u64);
+
+            if let Some(to_idx) = cfd_states.iter().position(|&s| s == to) {
+                let skipped_states: Vec<&str> = required_states
+                    .iter()
+                    .copied()
+                    .filter(|state| !visited.contains(state))
+                    .filter(|state| cfd_states[..to_idx].contains(state))
+                    .collect();
+                if !skipped_states.is_empty() {
+                    // only report each skipped state once
+                    visited.extend(&skipped_states);
+                    skips.push(StageSkip {
+                        task_gid: tgid,
+                        name: tgid2asana_task[tgid].name.as_str(),
+                        at: *at,
+                        from,
+                        to,
+                        skipped_states,
+                    });
+                }
+            }
+            visited.insert(to);
+        }
+    }
+    skips.sort_by_key(|skip| skip.at);
+
+    let state_idx = |state: &str| states.iter().position(|&s| s == state).unwrap();
+    let mut transitions: Vec<Transition> = transition2seconds
+        .into_iter()
+        .map(|((from, to), mut seconds)| {
+            seconds.sort_unstable();
+            Transition {
+                from,
+                to,
+                count: seconds.len() as u32,
+                median_seconds: percentile(&seconds, 50, interpolation),
+            }
+        })
+        .collect();
+    transitions.sort_by_key(|t| (state_idx(t.from), state_idx(t.to)));
+
+    Transitions {
+        states,
+        transitions,
+        required_states: required_states.to_vec(),
+        skips,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CFD_STATES: [&str; 4] = ["Todo", "Doing", "Review", "Done"];
+
+    fn task(gid: &str) -> AsanaTask {
+        serde_json::from_value(serde_json::json!({
+            "gid": gid,
+            "name": format!("Task {}", gid),
+            "created_at": "2024-01-01T00:00:00Z",
+            "completed": false,
+            "completed_at": null,
+            "assignee": null,
+            "memberships": [],
+        }))
+        .unwrap()
+    }
+
+    // (transitions as (from, to, count, median hours), skips as (task, skipped states))
+    type Summary = (
+        Vec<(String, String, u32, Option<u64>)>,
+        Vec<(String, Vec<String>)>,
+    );
+
+    fn transitions(events: &[(u32, &str, &str)], required_states: &[&str]) -> Summary {
+        let times: Vec<DateTime<Utc>> = events
+            .iter()
+            .map(|&(hour, _, _)| {
+                format!("2024-01-0{}T{:02}:00:00Z", 1 + hour / 24, hour % 24)
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        let events: Vec<TaskEvent> = times
+            .iter()
+            .zip(events)
+            .map(|(at, &(_, tgid, sname))| (at, tgid, sname))
+            .collect();
+        let tasks: Vec<AsanaTask> = vec![task("a"), task("b")];
+        let tgid2asana_task: HashMap<&str, &AsanaTask> =
+            tasks.iter().map(|t| (t.gid.as_str(), t)).collect();
+        let transitions = get_transitions(
+            &events,
+            &tgid2asana_task,
+            &CFD_STATES,
+            required_states,
+            Interpolation::Lower,
+            &"2024-01-01T00:00:00Z".parse().unwrap(),
+        );
+        (
+            transitions
+                .transitions
+                .iter()
+                .map(|t| {
+                    (
+                        t.from.to_owned(),
+                        t.to.to_owned(),
+                        t.count,
+                        t.median_seconds.map(|s| s / 3600),
+                    )
+                })
+                .collect(),
+            transitions
+                .skips
+                .iter()
+                .map(|s| {
+                    (
+                        s.task_gid.to_owned(),
+                        s.skipped_states.iter().map(|s| s.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn transition(
+        from: &str,
+        to: &str,
+        count: u32,
+        hours: u64,
+    ) -> (String, String, u32, Option<u64>) {
+        (from.to_owned(), to.to_owned(), count, Some(hours))
+    }
+
+    #[test]
+    fn counts_moves_between_states_in_board_order() {
+        let (transitions, skips) = transitions(
+            &[
+                (0, "a", "Todo"),
+                (0, "b", "Todo"),
+                (2, "a", "Doing"),
+                (4, "b", "Doing"),
+                (5, "a", "Review"),
+                (6, "a", "Doing"),
+            ],
+            &[],
+        );
+        assert_eq!(
+            transitions,
+            vec![
+                transition("Todo", "Doing", 2, 2),
+                transition("Doing", "Review", 1, 3),
+                transition("Review", "Doing", 1, 1),
+            ]
+        );
+        assert!(skips.is_empty());
+    }
+
+    #[test]
+    fn reports_each_skipped_required_state_once_per_task() {
+        let (_, skips) = transitions(
+            &[
+                (0, "a", "Todo"),
+                (1, "a", "Done"),
+                (2, "a", "Doing"),
+                (3, "a", "Done"),
+                (0, "b", "Todo"),
+                (1, "b", "Doing"),
+                (2, "b", "Review"),
+                (3, "b", "Done"),
+            ],
+            &["Doing", "Review"],
+        );
+        assert_eq!(
+            skips,
+            vec![(
+                "a".to_owned(),
+                vec!["Doing".to_owned(), "Review".to_owned()]
+            )]
+        );
+    }
+
+    #[test]
+    fn a_visited_required_state_is_not_skipped() {
+        let (_, skips) = transitions(
+            &[(0, "a", "Doing"), (1, "a", "Todo"), (2, "a", "Done")],
+            &["Doing", "Review"],
+        );
+        assert_eq!(skips, vec![("a".to_owned(), vec!["Review".to_owned()])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "State QA in required_states is not one of cfd_states")]
+    fn rejects_unknown_required_state() {
+        let config: MyProjectConfig = serde_json::from_str(
+            r#"{"gid": "1", "horizon": "2024-01-01T00:00:00Z",
+                "cfd_states": ["Todo", "Done"], "done_states": ["Done"],
+                "required_states": ["QA"]}"#,
+        )
+        .unwrap();
+        get_required_states(&config);
+    }
+}
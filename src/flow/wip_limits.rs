@@ -0,0 +1,130 @@
+use super::TaskEvent;
+use crate::config::MyProjectConfig;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The WIP limit of a state and the times it was exceeded during the report.
+#[derive(Debug, Serialize)]
+pub struct WipLimit<'a> {
+    pub state: &'a str,
+    pub limit: u32,
+    pub breaches: Vec<WipBreach>,
+    /// The total time the limit was exceeded.
+    pub breached_seconds: u64,
+}
+
+/// A time during which a state held more tasks than its WIP limit.
+#[derive(Debug, Serialize)]
+pub struct WipBreach {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub seconds: u64,
+    /// The most tasks in the state during the breach.
+    pub max_count: u32,
+    /// Whether the limit was still exceeded at the end of the report.
+    pub ongoing: bool,
+}
+
+/// The WIP limits of a project, in the order of `cfd_states`. Panics if a limit is
+/// set for a state that is not one of `cfd_states`.
+pub fn get_wip_limits(project_config: &MyProjectConfig) -> Vec<(&str, u32)> {
+    for state in project_config.wip_limits.keys() {
+        if !project_config.cfd_states.contains(state) {
+            panic!("State {} in wip_limits is not one of cfd_states", state);
+        }
+    }
+    project_config
+        .cfd_states
+        .iter()
+        .filter_map(|state| {
+            project_config
+                .wip_limits
+                .get(state)
+                .map(|&limit| (state.as_str(), limit))
+        })
+        .collect()
+}
+
+/// When each WIP limit was exceeded between `start` and `end`, to the second, given
+/// events sorted by time and ending at `end`. Events before `start` set the number
+/// of tasks in each state when the report starts.
+pub fn get_wip_breaches<'a>(
+    events: &[TaskEvent<'a>],
+    wip_limits: &[(&'a str, u32)],
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<WipLimit<'a>> {
+    let mut task_state: HashMap<&str, &str> = HashMap::new();
+    let mut state_count: HashMap<&str, u32> = HashMap::new();
+    // state => (start, max count) of the breach in progress
+    let mut open_breaches: HashMap<&str, (DateTime<Utc>, u32)> = HashMap::new();
+    let mut limits: Vec<WipLimit> = wip_limits
+        .iter()
+        .map(|&(state, limit)| WipLimit {
+            state,
+            limit,
+            breaches: Vec::new(),
+            breached_seconds: 0,
+        })
+        .collect();
+
+    let mut idx = 0;
+    while idx < events.len() {
+        // apply all the events at the same time before checking the limits, so that
+        // a task moving from one state to another does not look like a breach
+        let at = *events[idx].0;
+        while idx < events.len() && *events[idx].0 == at {
+            let (_, tgid, sname) = events[idx];
+            if let Some(old_state) = task_state.insert(tgid, sname) {
+                *state_count.get_mut(old_state).unwrap() -= 1;
+            }
+            *state_count.entry(sname).or_default() += 1;
+            idx += 1;
+        }
+        let at = std::cmp::max(at, *start);
+        for limit in limits.iter_mut() {
+            let count = state_count.get(limit.state).copied().unwrap_or(0);
+            match (count > limit.limit, open_breaches.get_mut(limit.state)) {
+                (true, Some((_, max_count))) => *max_count = std::cmp::max(*max_count, count),
+                (true, None) => {
+                    open_breaches.insert(limit.state, (at, count));
+                }
+                (false, Some(_)) => {
+                    let (breach_start, max_count) = open_breaches.remove(limit.state).unwrap();
+                    close_breach(limit, breach_start, at, max_count, false);
+                }
+                (false, None) => {}
+            }
+        }
+    }
+    for limit in limits.iter_mut() {
+        if let Some((breach_start, max_count)) = open_breaches.remove(limit.state) {
+            close_breach(limit, breach_start, *end, max_count, true);
+        }
+    }
+    limits
+}
+
+fn close_breach(
+    limit: &mut WipLimit,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    max_count: u32,
+    ongoing: bool,
+) {
+    // breaches that ended before the report started
+    if end <= start {
+        return;
+    }
+    let seconds = (end - start).num_seconds() as u64;
+    limit.breached_seconds += seconds;
+    limit.breaches.push(WipBreach {
+        start,
+        end,
+        seconds,
+        max_count,
+        ongoing,
+    });
+}
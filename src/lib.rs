@@ -1,2 +1,6 @@
 pub mod asana;
 pub mod config;
+pub mod flow;
+pub mod period;
+pub mod progress;
+pub mod token;
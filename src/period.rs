@@ -0,0 +1,194 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use serde::Deserialize;
+
+/// The length of the reporting periods that counts, durations and throughput are
+/// bucketed into. All periods start at midnight UTC. In the config file this is
+/// written as one of:
+///
+/// - `"daily"`
+/// - `{"weekly": {"start": "Wed"}}`: weeks starting on the given day (default Monday)
+/// - `{"sprint": {"weeks": 2, "anchor": "2024-01-03"}}`: N-week sprints, one of
+///   which starts on `anchor`
+/// - `"monthly"`: calendar months
+/// - `"quarterly"`: calendar quarters
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Daily,
+    Weekly {
+        #[serde(default = "default_week_start")]
+        start: Weekday,
+    },
+    Sprint {
+        weeks: u32,
+        anchor: NaiveDate,
+    },
+    Monthly,
+    Quarterly,
+}
+
+fn default_week_start() -> Weekday {
+    Weekday::Mon
+}
+
+impl Default for Period {
+    /// ISO weeks, starting on Monday.
+    fn default() -> Period {
+        Period::Weekly {
+            start: default_week_start(),
+        }
+    }
+}
+
+impl Period {
+    /// The start of the period containing `at`.
+    pub fn start_of(&self, at: &DateTime<Utc>) -> DateTime<Utc> {
+        let date = at.date_naive();
+        let start_date = match self {
+            Period::Daily => date,
+            Period::Weekly { start } => {
+                let days_since_start =
+                    (7 + date.weekday().num_days_from_monday() - start.num_days_from_monday()) % 7;
+                date - Duration::days(days_since_start as i64)
+            }
+            Period::Sprint { weeks, anchor } => {
+                let sprint_days = 7 * *weeks as i64;
+                let sprints_since_anchor = (date - *anchor).num_days().div_euclid(sprint_days);
+                *anchor + Duration::days(sprints_since_anchor * sprint_days)
+            }
+            Period::Monthly => NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap(),
+            Period::Quarterly => {
+                let quarter_month = (date.month() - 1) / 3 * 3 + 1;
+                NaiveDate::from_ymd_opt(date.year(), quarter_month, 1).unwrap()
+            }
+        };
+        start_date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+    }
+
+    /// The start of the period after the one starting at `start`.
+    pub fn next(&self, start: &DateTime<Utc>) -> DateTime<Utc> {
+        let date = start.date_naive();
+        let next_date = match self {
+            Period::Daily => date + Duration::days(1),
+            Period::Weekly { .. } => date + Duration::weeks(1),
+            Period::Sprint { weeks, .. } => date + Duration::weeks(*weeks as i64),
+            Period::Monthly => add_months(date, 1),
+            Period::Quarterly => add_months(date, 3),
+        };
+        next_date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+    }
+
+    /// Panics if the period is not usable, e.g. a sprint of zero weeks.
+    pub fn validate(&self) {
+        if let Period::Sprint { weeks: 0, .. } = self {
+            panic!("Sprint period must be at least one week long");
+        }
+    }
+}
+
+// `date` is always the first of a month here
+fn add_months(date: NaiveDate, months: u32) -> NaiveDate {
+    let month0 = date.month0() + months;
+    NaiveDate::from_ymd_opt(date.year() + (month0 / 12) as i32, month0 % 12 + 1, 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> DateTime<Utc> {
+        at(&format!("{}T00:00:00Z", s))
+    }
+
+    #[test]
+    fn daily() {
+        let period = Period::Daily;
+        assert_eq!(
+            period.start_of(&at("2024-03-05T17:30:00Z")),
+            date("2024-03-05")
+        );
+        assert_eq!(period.next(&date("2024-02-29")), date("2024-03-01"));
+    }
+
+    #[test]
+    fn weekly_from_monday_by_default() {
+        let period = Period::default();
+        // 2024-03-06 is a Wednesday
+        assert_eq!(
+            period.start_of(&at("2024-03-06T12:00:00Z")),
+            date("2024-03-04")
+        );
+        assert_eq!(period.start_of(&date("2024-03-04")), date("2024-03-04"));
+        assert_eq!(period.next(&date("2024-03-04")), date("2024-03-11"));
+    }
+
+    #[test]
+    fn weekly_from_another_day() {
+        let period = Period::Weekly {
+            start: Weekday::Wed,
+        };
+        // Tuesday, Wednesday and Sunday
+        assert_eq!(period.start_of(&date("2024-03-05")), date("2024-02-28"));
+        assert_eq!(period.start_of(&date("2024-03-06")), date("2024-03-06"));
+        assert_eq!(period.start_of(&date("2024-03-10")), date("2024-03-06"));
+        assert_eq!(period.next(&date("2024-03-06")), date("2024-03-13"));
+    }
+
+    #[test]
+    fn sprint_after_anchor() {
+        let period = Period::Sprint {
+            weeks: 2,
+            anchor: NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+        };
+        assert_eq!(period.start_of(&date("2024-01-03")), date("2024-01-03"));
+        assert_eq!(period.start_of(&date("2024-01-16")), date("2024-01-03"));
+        assert_eq!(period.start_of(&date("2024-01-17")), date("2024-01-17"));
+        assert_eq!(period.next(&date("2024-01-17")), date("2024-01-31"));
+    }
+
+    #[test]
+    fn sprint_before_anchor() {
+        let period = Period::Sprint {
+            weeks: 2,
+            anchor: NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+        };
+        assert_eq!(period.start_of(&date("2024-01-02")), date("2023-12-20"));
+        assert_eq!(period.start_of(&date("2023-12-20")), date("2023-12-20"));
+        assert_eq!(period.start_of(&date("2023-12-19")), date("2023-12-06"));
+    }
+
+    #[test]
+    fn monthly() {
+        let period = Period::Monthly;
+        assert_eq!(
+            period.start_of(&at("2024-02-29T23:59:59Z")),
+            date("2024-02-01")
+        );
+        assert_eq!(period.next(&date("2024-01-01")), date("2024-02-01"));
+        assert_eq!(period.next(&date("2024-12-01")), date("2025-01-01"));
+    }
+
+    #[test]
+    fn quarterly() {
+        let period = Period::Quarterly;
+        assert_eq!(period.start_of(&date("2024-03-31")), date("2024-01-01"));
+        assert_eq!(period.start_of(&date("2024-04-01")), date("2024-04-01"));
+        assert_eq!(period.start_of(&date("2024-12-31")), date("2024-10-01"));
+        assert_eq!(period.next(&date("2024-07-01")), date("2024-10-01"));
+        assert_eq!(period.next(&date("2024-10-01")), date("2025-01-01"));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one week")]
+    fn rejects_empty_sprint() {
+        Period::Sprint {
+            weeks: 0,
+            anchor: NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+        }
+        .validate();
+    }
+}
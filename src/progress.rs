@@ -0,0 +1,165 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// redraw the progress line at most this often
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Live progress of a `fetch` run, drawn on a single line of stderr when stderr
+/// is a terminal. Counters can be updated from concurrent futures.
+pub struct Progress {
+    started: Instant,
+    enabled: bool,
+    // combined rate limit of all clients, in requests per second
+    max_rps: f64,
+    projects_total: AtomicU64,
+    projects_done: AtomicU64,
+    tasks_total: AtomicU64,
+    tasks_done: AtomicU64,
+    story_lists_done: AtomicU64,
+    stories_done: AtomicU64,
+    tasks_skipped: AtomicU64,
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    pub fn new(max_rps: f64) -> Progress {
+        Progress {
+            started: Instant::now(),
+            enabled: std::io::stderr().is_terminal(),
+            max_rps,
+            projects_total: AtomicU64::new(0),
+            projects_done: AtomicU64::new(0),
+            tasks_total: AtomicU64::new(0),
+            tasks_done: AtomicU64::new(0),
+            story_lists_done: AtomicU64::new(0),
+            stories_done: AtomicU64::new(0),
+            tasks_skipped: AtomicU64::new(0),
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    pub fn add_projects(&self, count: u64) {
+        self.projects_total.fetch_add(count, Ordering::Relaxed);
+        self.draw(false);
+    }
+
+    pub fn project_done(&self) {
+        self.projects_done.fetch_add(1, Ordering::Relaxed);
+        self.draw(false);
+    }
+
+    pub fn add_tasks(&self, count: u64) {
+        self.tasks_total.fetch_add(count, Ordering::Relaxed);
+        self.draw(false);
+    }
+
+    pub fn task_done(&self) {
+        self.tasks_done.fetch_add(1, Ordering::Relaxed);
+        self.draw(false);
+    }
+
+    pub fn stories_done(&self, count: u64) {
+        self.story_lists_done.fetch_add(1, Ordering::Relaxed);
+        self.stories_done.fetch_add(count, Ordering::Relaxed);
+        self.draw(false);
+    }
+
+    pub fn task_skipped(&self) {
+        self.tasks_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn tasks_skipped(&self) -> u64 {
+        self.tasks_skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Draws the final state and moves to a new line, so that later output is
+    /// not mixed with the progress line.
+    pub fn finish(&self) {
+        if self.enabled {
+            self.draw(true);
+            eprintln!();
+        }
+    }
+
+    // Estimated time left, assuming every remaining task needs one request for
+    // the task and one for its stories, all at the rate limit.
+    fn eta(&self) -> Option<Duration> {
+        if self.max_rps <= 0.0 {
+            return None;
+        }
+        let tasks_total = self.tasks_total.load(Ordering::Relaxed);
+        let remaining_requests = tasks_total
+            .saturating_sub(self.tasks_done.load(Ordering::Relaxed))
+            + tasks_total.saturating_sub(self.story_lists_done.load(Ordering::Relaxed));
+        Some(Duration::from_secs_f64(
+            remaining_requests as f64 / self.max_rps,
+        ))
+    }
+
+    fn draw(&self, force: bool) {
+        if !self.enabled {
+            return;
+        }
+        {
+            let mut last_draw = self.last_draw.lock().unwrap();
+            let now = Instant::now();
+            if !force && last_draw.is_some_and(|t| now - t < REDRAW_INTERVAL) {
+                return;
+            }
+            *last_draw = Some(now);
+        }
+        let eta = self
+            .eta()
+            .map(format_duration)
+            .unwrap_or_else(|| String::from("?"));
+        let line = format!(
+            "projects {}/{} | tasks {}/{} | stories {} | elapsed {} | ETA {}",
+            self.projects_done.load(Ordering::Relaxed),
+            self.projects_total.load(Ordering::Relaxed),
+            self.tasks_done.load(Ordering::Relaxed),
+            self.tasks_total.load(Ordering::Relaxed),
+            self.stories_done.load(Ordering::Relaxed),
+            format_duration(self.elapsed()),
+            eta
+        );
+        let mut stderr = std::io::stderr();
+        // `\x1b[K` clears what is left of a previous, longer line
+        let _ = write!(stderr, "\r{}\x1b[K", line);
+        let _ = stderr.flush();
+    }
+}
+
+/// Formats a duration as e.g. `1h02m03s`, `2m03s` or `3s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}h{:02}m{:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m{:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// Formats a byte count as e.g. `512 B`, `1.5 KiB` or `3.2 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
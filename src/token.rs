@@ -0,0 +1,119 @@
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Environment variable consulted when neither the command line nor the config
+/// specify where the Asana Personal Access Token comes from.
+pub static DEFAULT_TOKEN_ENV: &str = "ASANA_TOKEN";
+
+/// An Asana Personal Access Token.
+///
+/// The value is never printed: `Debug` is redacted, and there is no `Display`.
+pub struct Token(String);
+
+impl Token {
+    pub fn new(token: &str) -> Token {
+        Token(token.trim().to_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Token(<redacted>)")
+    }
+}
+
+/// Where to obtain a token from. In the config file this is written as one of:
+///
+/// - `{"env": "ASANA_TOKEN"}`: the value of an environment variable
+/// - `{"file": "/path/to/token"}`: the contents of a file
+/// - `{"command": ["secret-tool", "lookup", "asana", "pat"]}`: the stdout of a command
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenSource {
+    Env(String),
+    File(PathBuf),
+    Command(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum TokenError {
+    EnvMissing(String),
+    File(PathBuf, io::Error),
+    CommandMissing,
+    CommandSpawn(String, io::Error),
+    CommandFailed(String, ExitStatus),
+    NotUtf8(TokenSource),
+    Empty(TokenSource),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::EnvMissing(var) => write!(f, "environment variable {} is not set", var),
+            TokenError::File(path, err) => {
+                write!(f, "could not read token file {}: {}", path.display(), err)
+            }
+            TokenError::CommandMissing => write!(f, "token command is empty"),
+            TokenError::CommandSpawn(program, err) => {
+                write!(f, "could not run token command {}: {}", program, err)
+            }
+            TokenError::CommandFailed(program, status) => {
+                write!(f, "token command {} failed: {}", program, status)
+            }
+            TokenError::NotUtf8(source) => write!(f, "token from {:?} is not UTF-8", source),
+            TokenError::Empty(source) => write!(f, "token from {:?} is empty", source),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+impl TokenSource {
+    /// The source used when nothing else is configured: `$ASANA_TOKEN`.
+    pub fn default_env() -> TokenSource {
+        TokenSource::Env(DEFAULT_TOKEN_ENV.to_owned())
+    }
+
+    pub fn resolve(&self) -> Result<Token, TokenError> {
+        let raw: Vec<u8> = match self {
+            TokenSource::Env(var) => std::env::var_os(var)
+                .ok_or_else(|| TokenError::EnvMissing(var.clone()))?
+                .into_string()
+                .map_err(|_| TokenError::NotUtf8(self.clone()))?
+                .into_bytes(),
+            TokenSource::File(path) => {
+                fs::read(path).map_err(|err| TokenError::File(path.clone(), err))?
+            }
+            TokenSource::Command(argv) => {
+                let (program, args) = argv.split_first().ok_or(TokenError::CommandMissing)?;
+                log::debug!("TokenSource::resolve: running {}", program);
+                // stderr is inherited so that helpers can prompt or report problems;
+                // stdout carries the token and is never logged
+                let output = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::inherit())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .map_err(|err| TokenError::CommandSpawn(program.clone(), err))?;
+                if !output.status.success() {
+                    return Err(TokenError::CommandFailed(program.clone(), output.status));
+                }
+                output.stdout
+            }
+        };
+        let token_str = String::from_utf8(raw).map_err(|_| TokenError::NotUtf8(self.clone()))?;
+        let token = Token::new(&token_str);
+        if token.as_str().is_empty() {
+            return Err(TokenError::Empty(self.clone()));
+        }
+        Ok(token)
+    }
+}